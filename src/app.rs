@@ -1,22 +1,36 @@
 use anyhow::Result;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
+    cursor::SetCursorStyle,
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture, Event, KeyCode,
+        KeyEventKind, KeyModifiers,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{
     backend::CrosstermBackend,
+    layout::Rect,
     Terminal,
 };
+use std::collections::HashMap;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tokio::task::JoinHandle;
 use tokio::time::Duration;
 
-use crate::config::Config;
-use crate::core::{BufferManager, Buffer};
-use crate::modes::{Mode, ModeManager};
-use crate::ui::{UI, Dashboard};
-use crate::picker::Picker;
+use crate::config::{Config, ForgetQuery, StartupScreen};
+use crate::core::buffer::DetectedIndent;
+use crate::core::session::{BufferSession, SessionData, SessionManager};
+use crate::core::{project, BufferManager, Position};
+use crate::lsp::{LspEvent, LspManager};
+use crate::terminal::{TerminalJob, TerminalOutput};
+use crate::modes::{Mode, ModeManager, ModeTransition};
+use crate::ui::{UI, Dashboard, Explorer};
+use crate::ui::explorer::{self, ExplorerAction};
+use crate::ui::dashboard::ProjectInfo;
+use crate::picker::{Picker, PickerAction, PickerHistory};
 
 pub struct App {
     terminal: Terminal<CrosstermBackend<io::Stdout>>,
@@ -26,35 +40,66 @@ pub struct App {
     ui: UI,
     picker: Option<Picker>,
     dashboard: Option<Dashboard>,
+    explorer: Option<Explorer>,
+    // The query each kind of picker was last closed with, by picker title
+    picker_queries: HashMap<&'static str, String>,
+    // Queries that led to a selection, by picker title
+    picker_history: PickerHistory,
+    session_manager: SessionManager,
+    zen_mode_active: bool,
     should_quit: bool,
+    // The directory `config.toml` was loaded from, if the config is reloaded
+    // when that file is written
+    config_dir: Option<PathBuf>,
+    // `--noplugin`: reloads leave out autocommands and mappings too
+    noplugin: bool,
+    config_reload: Option<JoinHandle<Result<Config>>>,
+    // Ex commands from `--startup-cmd` and `+`, run after the first draw
+    startup_commands: Vec<String>,
+    lsp: LspManager,
+    // `:terminal` commands still printing into their buffers
+    terminal_jobs: Vec<TerminalJob>,
 }
 
 impl App {
-    pub fn new(files: Vec<PathBuf>, config_path: Option<PathBuf>) -> Result<Self> {
+    /// Start the editor on `files`, running the ex commands in `cmds` before
+    /// any of them are opened and those in `startup_commands` once they have
+    /// been drawn
+    pub fn new(files: Vec<PathBuf>, config: Config, cmds: &[String], startup_commands: Vec<String>) -> Result<Self> {
         // Setup terminal
         enable_raw_mode()?;
         let mut stdout = io::stdout();
-        execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+        execute!(stdout, EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste)?;
         let backend = CrosstermBackend::new(stdout);
         let terminal = Terminal::new(backend)?;
-
-        // Load configuration
-        let config = Config::load(config_path)?;
         
         // Initialize components
         let mut buffer_manager = BufferManager::new();
+        let mut mode_manager = ModeManager::new(&config);
+        
+        // Their errors show as messages
+        for cmd in cmds {
+            let _ = mode_manager.run_command(cmd, &mut buffer_manager);
+        }
         
         // Open files if provided, otherwise create empty buffer
         if files.is_empty() {
             buffer_manager.create_buffer("untitled".to_string());
         } else {
             for file in files {
-                buffer_manager.open_file(file)?;
+                mode_manager.open_file(&file, &mut buffer_manager);
+            }
+            if buffer_manager.is_empty() {
+                // None of the files could be opened
+                buffer_manager.create_buffer("untitled".to_string());
             }
         }
         
-        let mode_manager = ModeManager::new();
         let ui = UI::new(&config);
+        let zen_mode_active = config.ui.zen_mode;
+        let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        let root = project::find_root(&cwd, project::ROOT_MARKERS).unwrap_or(cwd);
+        let lsp = LspManager::new(&config.lsp, root);
         
         Ok(Self {
             terminal,
@@ -64,22 +109,74 @@ impl App {
             ui,
             picker: None,
             dashboard: None,
+            explorer: None,
+            picker_queries: HashMap::new(),
+            picker_history: PickerHistory::load(),
+            session_manager: SessionManager::new(),
+            zen_mode_active,
             should_quit: false,
+            config_dir: None,
+            noplugin: false,
+            config_reload: None,
+            startup_commands,
+            lsp,
+            terminal_jobs: Vec::new(),
         })
     }
     
-    pub fn should_show_dashboard(&self) -> bool {
-        // Show dashboard if explicitly requested with -D flag
-        false
+    /// Show what `ui.startup_screen` asks for. Without `allow_dashboard`
+    /// the dashboard is skipped.
+    pub async fn show_startup_screen(&mut self, allow_dashboard: bool) -> Result<()> {
+        match self.config.ui.startup_screen {
+            StartupScreen::Dashboard if allow_dashboard => self.show_dashboard().await,
+            StartupScreen::Dashboard | StartupScreen::Empty => Ok(()),
+            StartupScreen::LastSession => self.resume_session(),
+        }
+    }
+    
+    /// Reload the config from `config_dir` whenever its `config.toml` is
+    /// written from the editor and `ui.reload_on_write` is on. With
+    /// `noplugin`, reloads leave out autocommands and mappings.
+    pub fn watch_config(&mut self, config_dir: PathBuf, noplugin: bool) {
+        self.config_dir = Some(config_dir);
+        self.noplugin = noplugin;
     }
     
     pub async fn show_dashboard(&mut self) -> Result<()> {
-        self.dashboard = Some(Dashboard::new(&self.config));
+        self.dashboard = Some(self.new_dashboard());
         Ok(())
     }
     
+    // The dashboard with the recent files of earlier sessions
+    fn new_dashboard(&self) -> Dashboard {
+        let recent_files = self.session_manager.recent_files(self.config.dashboard.max_recent_files);
+        Dashboard::new(&self.config, Self::project_info(), recent_files)
+    }
+    
+    // Name and git branch of the project around the working directory
+    fn project_info() -> Option<ProjectInfo> {
+        let root = project::find_root(&std::env::current_dir().ok()?, project::ROOT_MARKERS)?;
+        let name = project_name(&root).or_else(|| {
+            root.file_name().map(|name| name.to_string_lossy().into_owned())
+        })?;
+        let branch = Command::new("git")
+            .args(["branch", "--show-current"])
+            .current_dir(&root)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+            .filter(|branch| !branch.is_empty());
+        
+        Some(ProjectInfo { name, branch })
+    }
+    
     pub async fn run(&mut self) -> Result<()> {
         loop {
+            let height = self.terminal.size().map_or(0, |size| usize::from(size.height));
+            self.buffer_manager.refresh_syntax(height);
+            self.buffer_manager.refresh_folds(&self.config);
+            
             // Draw UI
             self.terminal.draw(|frame| {
                 if let Some(ref dashboard) = self.dashboard {
@@ -92,70 +189,202 @@ impl App {
                         &self.buffer_manager,
                         &self.mode_manager,
                         frame.size(),
+                        self.zen_mode_active,
+                        self.explorer.as_ref(),
                     );
                 }
             })?;
             
+            // Commands from `--startup-cmd` and `+` see the files as first drawn
+            if !self.startup_commands.is_empty() {
+                if self.run_startup_commands().await? {
+                    break;
+                }
+                continue;
+            }
+            
             // Handle events
             if self.handle_events().await? {
                 break;
             }
+            self.reload_config_if_written();
+            self.finish_config_reload().await;
+            self.sync_language_servers();
+            self.read_terminal_output();
             
             if self.should_quit {
                 break;
             }
         }
         
+        if self.config.session.auto_save {
+            self.save_session()?;
+        }
+        Ok(())
+    }
+    
+    /// Reopen the files of the last session with their cursors where they
+    /// were. The empty buffer the editor starts with makes way for them.
+    fn resume_session(&mut self) -> Result<()> {
+        let session = match self.session_manager.load() {
+            Ok(Some(session)) => session,
+            Ok(None) => {
+                self.mode_manager.show_message("No session to resume");
+                return Ok(());
+            }
+            Err(e) => {
+                self.mode_manager.show_message(format!("Can't read the last session: {}", e));
+                return Ok(());
+            }
+        };
+        let scratch = self
+            .buffer_manager
+            .current_buffer()
+            .filter(|buffer| buffer.path.is_none() && !buffer.modified)
+            .map(|buffer| buffer.id);
+        
+        if let Some(ref dir) = session.last_directory {
+            // Like `:cd`, so the pickers search where they did
+            let _ = std::env::set_current_dir(dir);
+        }
+        
+        let mut current = None;
+        let mut opened = 0;
+        for saved in &session.buffers {
+            let Some(ref path) = saved.path else {
+                continue;
+            };
+            self.mode_manager.open_file(path, &mut self.buffer_manager);
+            let Some(id) = self.buffer_manager.find_buffer_by_path(path) else {
+                continue;
+            };
+            opened += 1;
+            if let Some(buffer) = self.buffer_manager.current_buffer_mut() {
+                buffer.cursor.move_to_position(Position { row: saved.cursor_row, col: saved.cursor_col });
+                buffer.clamp_cursor();
+                if !saved.folds.is_empty() {
+                    let indent = buffer.indent.unwrap_or_else(|| DetectedIndent::from_config(&self.config));
+                    buffer.restore_folds(saved.folds.clone(), indent.tab_width);
+                }
+            }
+            if session.current_buffer_id == Some(saved.id) {
+                current = Some(id);
+            }
+        }
+        
+        if opened == 0 {
+            self.mode_manager.show_message("No files in the last session could be opened");
+            return Ok(());
+        }
+        if let Some(id) = current {
+            self.buffer_manager.switch_buffer(id);
+        }
+        if let Some(id) = scratch {
+            self.buffer_manager.close_buffer(id, false)?;
+        }
+        // Their changes were never written, so they are gone
+        let unsaved = session.buffers.iter().filter(|saved| saved.modified).count();
+        if unsaved > 0 {
+            self.mode_manager.show_message(format!("{} buffer(s) had unsaved changes when the session was saved", unsaved));
+        }
         Ok(())
     }
     
+    // Record the open buffers, current one first, for the next dashboard,
+    // leaving out what `[session]` says not to save
+    fn save_session(&self) -> Result<()> {
+        let options = &self.config.session;
+        let current = self.buffer_manager.current_buffer_id();
+        let mut buffers = self.buffer_manager.list_buffers();
+        buffers.sort_by_key(|buffer| Some(buffer.id) != current);
+        let buffers = buffers
+            .into_iter()
+            .map(|buffer| {
+                let position = if options.save_cursor { buffer.cursor.position() } else { Position { row: 0, col: 0 } };
+                BufferSession {
+                    id: buffer.id,
+                    path: buffer.path.as_ref().map(|path| path.canonicalize().unwrap_or_else(|_| path.clone())),
+                    cursor_row: position.row,
+                    cursor_col: position.col,
+                    modified: options.save_modified && buffer.modified,
+                    folds: if options.save_folds {
+                        buffer.folds.iter().filter(|fold| fold.manual || fold.closed).copied().collect()
+                    } else {
+                        Vec::new()
+                    },
+                }
+            })
+            .collect();
+        self.session_manager.save_with_recent_files(SessionData {
+            buffers,
+            current_buffer_id: current,
+            last_directory: std::env::current_dir().ok().filter(|_| options.save_directory),
+            recent_files: Vec::new(),
+        })
+    }
+    
     async fn handle_events(&mut self) -> Result<bool> {
         // Check for events without blocking
         if event::poll(Duration::from_millis(0))? {
             match event::read()? {
-                Event::Key(key) => {
-                    if key.kind == KeyEventKind::Press {
-                        return self.handle_key_event(key).await;
-                    }
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    return self.handle_key_event(key).await;
                 }
-                Event::Resize(_, _) => {
-                    // Handle terminal resize
+                Event::Resize(width, height) => {
+                    self.on_resize(width, height)?;
                 }
+                Event::Paste(text) => self.handle_paste(&text)?,
                 _ => {}
             }
+        } else if let Some(transition) = self.mode_manager.handle_timeout(&mut self.buffer_manager)? {
+            self.on_mode_change(transition)?;
+        }
+        // A mapping the timeout completed may have asked for one
+        if let Some(action) = self.mode_manager.take_action() {
+            return self.run_action(&action).await;
         }
         Ok(false)
     }
     
+    // Text the terminal pasted goes into insert mode as it is, skipping the
+    // mappings and indenting typed keys would go through. Anywhere else
+    // it rings the bell.
+    fn handle_paste(&mut self, text: &str) -> Result<()> {
+        let explorer_focused = self.shown_explorer().is_some_and(|explorer| explorer.focused);
+        let pasted = self.dashboard.is_none()
+            && self.picker.is_none()
+            && !explorer_focused
+            && self.mode_manager.paste(text, &mut self.buffer_manager);
+        if !pasted {
+            let backend = self.terminal.backend_mut();
+            io::Write::write_all(backend, b"\x07")?;
+            io::Write::flush(backend)?;
+        }
+        Ok(())
+    }
+    
+    /// Adapt to a new terminal size: tell the backend, and scroll every
+    /// buffer so its cursor stays in view. The picker splits its area on
+    /// every draw, so it needs nothing.
+    fn on_resize(&mut self, width: u16, height: u16) -> Result<()> {
+        self.terminal.resize(Rect::new(0, 0, width, height))?;
+        let explorer = self.explorer.as_ref().and_then(|explorer| explorer.shown_width(width));
+        // The bottom row is the status or command line
+        let rows = usize::from(height.saturating_sub(1));
+        self.buffer_manager.fit_views(rows, usize::from(width - explorer.unwrap_or(0)));
+        Ok(())
+    }
+    
     async fn handle_key_event(&mut self, key: crossterm::event::KeyEvent) -> Result<bool> {
         // Handle dashboard
         if let Some(ref mut dashboard) = self.dashboard {
             match dashboard.handle_key(key) {
                 Some(action) => {
-                    match action.as_str() {
-                        "quit" => return Ok(true),
-                        "files" => {
-                            self.dashboard = None;
-                            self.show_file_picker().await?;
-                        }
-                        "grep" => {
-                            self.dashboard = None;
-                            self.show_grep_picker().await?;
-                        }
-                        "buffers" => {
-                            self.dashboard = None;
-                            self.show_buffer_picker().await?;
-                        }
-                        "resume" => {
-                            self.dashboard = None;
-                            self.buffer_manager.resume_session()?;
-                        }
-                        "rename" => {
-                            self.dashboard = None;
-                            self.buffer_manager.rename_current_file()?;
-                        }
-                        _ => {}
-                    }
+                    self.dashboard = None;
+                    return self.run_action(&action).await;
+                }
+                None if dashboard.has_pending_keys() => {
+                    // Waiting for the rest of a multi-key menu sequence
                 }
                 None => {
                     // Close dashboard on any other key
@@ -168,44 +397,356 @@ impl App {
         // Handle picker
         if let Some(ref mut picker) = self.picker {
             match picker.handle_key(key).await? {
-                Some(result) => {
+                PickerAction::Continue => {}
+                PickerAction::Cancel => {
+                    self.remember_picker_query(ForgetQuery::Esc);
+                    self.picker = None;
+                }
+                PickerAction::DeleteBuffer(buffer_id) => {
+                    self.delete_buffer_from_picker(buffer_id).await?;
+                }
+                PickerAction::Select(result) => {
+                    self.remember_picker_query(ForgetQuery::Enter);
+                    self.record_picker_history();
                     self.picker = None;
                     if let Some(buffer_id) = result.selected_buffer_id {
                         // Switch to existing buffer
                         self.buffer_manager.switch_buffer(buffer_id);
                     } else if let Some(path) = result.selected_file {
                         // Open new file
-                        self.buffer_manager.open_file(path)?;
+                        self.mode_manager.open_file(&path, &mut self.buffer_manager);
+                    } else if let Some(word) = result.search_word {
+                        self.mode_manager.search_word(&word, result.search_from_start, &mut self.buffer_manager);
+                    } else if let Some(row) = result.selected_line {
+                        if let Some(buffer) = self.buffer_manager.current_buffer_mut() {
+                            buffer.cursor.move_to_position(Position { row, col: 0 });
+                            buffer.clamp_cursor();
+                        }
+                    } else if let Some(replacement) = result.replacement {
+                        self.replace_word_under_cursor(&replacement);
                     }
                 }
-                None => {
-                    self.picker = None;
-                }
             }
             return Ok(false);
         }
         
+        // Messages only last until the next key
+        self.mode_manager.clear_message();
+        
+        // A focused explorer takes every key but the leader and window
+        // commands, as long as it is on screen
+        let window_key = self.mode_manager.window_pending()
+            || (key.code == KeyCode::Char('w') && key.modifiers.contains(KeyModifiers::CONTROL));
+        if key.code != KeyCode::Char(' ') && !window_key {
+            if let Some(explorer) = self.shown_explorer().filter(|explorer| explorer.focused) {
+                match explorer.handle_key(key) {
+                    ExplorerAction::Continue => {}
+                    ExplorerAction::Unfocus => explorer.focused = false,
+                    ExplorerAction::Close => self.explorer = None,
+                    ExplorerAction::Open(path) => {
+                        explorer.focused = false;
+                        self.mode_manager.open_file(&path, &mut self.buffer_manager);
+                    }
+                }
+                return Ok(false);
+            }
+        }
+        
         // Handle normal editor keys
         match key.code {
-            KeyCode::Char('q') if self.mode_manager.current_mode() == Mode::Normal => {
+            KeyCode::Char('q') if self.mode_manager.current_mode() == Mode::Normal && !window_key => {
                 return Ok(true); // Quit
             }
             KeyCode::Char(' ') if self.mode_manager.current_mode() == Mode::Normal => {
                 // Leader key - only in normal mode
                 return self.handle_leader_key().await;
             }
+            KeyCode::Esc
+                if self.zen_mode_active
+                    && self.mode_manager.current_mode() == Mode::Normal
+                    && !self.mode_manager.has_pending_keys() =>
+            {
+                // Nothing left to escape from - leave zen mode
+                self.zen_mode_active = false;
+            }
             _ => {
                 // Pass to mode manager (handles insert, visual, command modes)
-                self.mode_manager.handle_key(key, &mut self.buffer_manager)?;
+                if let Some(transition) = self.mode_manager.handle_key(key, &mut self.buffer_manager)? {
+                    self.on_mode_change(transition)?;
+                }
+                if let Some(action) = self.mode_manager.take_action() {
+                    return self.run_action(&action).await;
+                }
             }
         }
         
         Ok(false)
     }
     
+    // Run the `--startup-cmd` and `+` commands in order, stopping if one quits
+    async fn run_startup_commands(&mut self) -> Result<bool> {
+        for command in std::mem::take(&mut self.startup_commands) {
+            // An error shows as a message and the next command still runs
+            let _ = self.mode_manager.run_command(&command, &mut self.buffer_manager);
+            if let Some(action) = self.mode_manager.take_action() {
+                if self.run_action(&action).await? {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+    
+    // Start reading the config again in the background if the last key
+    // wrote the config file
+    fn reload_config_if_written(&mut self) {
+        let written = self.mode_manager.take_written();
+        let Some(ref config_dir) = self.config_dir else {
+            return;
+        };
+        if !self.config.ui.reload_on_write || self.config_reload.is_some() {
+            return;
+        }
+        let config_file = Config::file_path(Some(config_dir.clone()));
+        if written.iter().any(|path| same_file(path, &config_file)) {
+            let config_dir = config_dir.clone();
+            let noplugin = self.noplugin;
+            self.config_reload = Some(tokio::task::spawn_blocking(move || {
+                Config::load(Some(config_dir)).map(|config| if noplugin { config.without_plugins() } else { config })
+            }));
+        }
+    }
+    
+    // Apply a finished reload. A config that doesn't parse leaves the
+    // current one in place.
+    async fn finish_config_reload(&mut self) {
+        let Some(task) = self.config_reload.take_if(|task| task.is_finished()) else {
+            return;
+        };
+        match task.await {
+            Ok(Ok(config)) => {
+                self.apply_config(config);
+                self.mode_manager.show_message("Config reloaded");
+            }
+            Ok(Err(e)) => self.mode_manager.show_message(format!("Config reload failed: {}", e)),
+            Err(e) => self.mode_manager.show_message(format!("Config reload failed: {}", e)),
+        }
+    }
+    
+    /// Switch every part of the editor over to `config`. Open pickers and
+    /// the dashboard keep the config they were opened with.
+    pub fn apply_config(&mut self, config: Config) {
+        self.ui.set_config(&config);
+        self.mode_manager.set_config(&config);
+        self.lsp.set_config(&config.lsp);
+        self.config = config;
+    }
+    
+    // Run `command` for `:terminal`, in a new buffer named after it
+    fn start_terminal(&mut self, command: &str) {
+        let buffer_id = self.buffer_manager.create_buffer(format!("!{}", command));
+        match TerminalJob::spawn(command, buffer_id) {
+            Ok(job) => self.terminal_jobs.push(job),
+            Err(e) => self.mode_manager.show_message(format!("E903: Process failed to start: {}", e)),
+        }
+    }
+    
+    // Put what the `:terminal` commands printed into their buffers. A job
+    // whose buffer was closed is dropped, which kills its command.
+    fn read_terminal_output(&mut self) {
+        let buffer_manager = &mut self.buffer_manager;
+        self.terminal_jobs.retain_mut(|job| {
+            let Some(buffer) = buffer_manager.buffer_mut(job.buffer_id) else {
+                return false;
+            };
+            let (output, done) = job.poll();
+            let lines = output
+                .into_iter()
+                .map(|output| match output {
+                    TerminalOutput::Line(line) => line,
+                    TerminalOutput::Exited(Some(code)) => format!("[Process exited {}]", code),
+                    TerminalOutput::Exited(None) => "[Process killed]".to_string(),
+                })
+                .collect();
+            buffer.append_output(lines);
+            !done
+        });
+    }
+    
+    /// Send buffer changes to the language servers and take in what they
+    /// reported since the last time
+    fn sync_language_servers(&mut self) {
+        self.lsp.sync(self.buffer_manager.list_buffers());
+        for event in self.lsp.poll() {
+            match event {
+                LspEvent::Diagnostics { path, diagnostics } => {
+                    let id = self.buffer_manager.find_buffer_by_path(&path);
+                    if let Some(buffer) = id.and_then(|id| self.buffer_manager.buffer_mut(id)) {
+                        buffer.diagnostics = diagnostics.iter().map(|diagnostic| diagnostic.resolve(&buffer.content)).collect();
+                    }
+                }
+                LspEvent::InlayHints { path, hints } => {
+                    let id = self.buffer_manager.find_buffer_by_path(&path);
+                    if let Some(buffer) = id.and_then(|id| self.buffer_manager.buffer_mut(id)) {
+                        buffer.inlay_hints.clear();
+                        for hint in &hints {
+                            let (row, hint) = hint.resolve(&buffer.content);
+                            buffer.inlay_hints.entry(row).or_default().push(hint);
+                        }
+                    }
+                }
+                LspEvent::Stopped(message) => self.mode_manager.show_message(message),
+            }
+        }
+    }
+    
+    /// React to a mode change reported by the mode manager
+    fn on_mode_change(&mut self, transition: ModeTransition) -> Result<()> {
+        // Bar cursor while inserting text, block everywhere else
+        let cursor_style = match transition.to {
+            Mode::Insert => SetCursorStyle::SteadyBar,
+            _ => SetCursorStyle::SteadyBlock,
+        };
+        execute!(self.terminal.backend_mut(), cursor_style)?;
+        Ok(())
+    }
+    
+    /// Run a named action requested by the dashboard or the mode manager.
+    /// Returns true if the editor should quit.
+    async fn run_action(&mut self, action: &str) -> Result<bool> {
+        match action {
+            "quit" => return Ok(true),
+            "files" => self.show_file_picker().await?,
+            "grep" => self.show_grep_picker().await?,
+            "buffers" => self.show_buffer_picker().await?,
+            "resume" => self.resume_session()?,
+            "rename" => self.buffer_manager.rename_current_file()?,
+            "help" => self.show_help(),
+            "inlay_hints" => {
+                let requested = self.buffer_manager.current_buffer().is_some_and(|buffer| self.lsp.request_inlay_hints(buffer));
+                if !requested {
+                    self.mode_manager.show_message("No language server attached to this buffer");
+                }
+            }
+            "messages" => self.show_messages(),
+            "zen_mode" => self.zen_mode_active = !self.zen_mode_active,
+            // The explorer is the only other window there is
+            "only" if self.explorer.is_some() => self.explorer = None,
+            // The explorer closes; the editor is the last window
+            "close_window" | "quit_window" if self.shown_explorer().is_some_and(|explorer| explorer.focused) => {
+                self.explorer = None;
+            }
+            "close_window" => self.mode_manager.show_message("E444: Cannot close last window"),
+            // Refuse to drop unsaved changes, as Vim's `:q` does
+            "quit_window" if self.buffer_manager.list_buffers().iter().any(|buffer| buffer.modified) => {
+                self.mode_manager.show_message("E37: No write since last change (add ! to override)");
+            }
+            "quit_window" => return Ok(true),
+            "window_left" | "window_next" if self.shown_explorer().is_some_and(|explorer| !explorer.focused) => {
+                if let Some(explorer) = self.explorer.as_mut() {
+                    explorer.focused = true;
+                }
+            }
+            "window_right" | "window_next" => {
+                if let Some(explorer) = self.explorer.as_mut() {
+                    explorer.focused = false;
+                }
+            }
+            "window_left" => {}
+            "only" => self.mode_manager.show_message("Already only one window"),
+            "options" => self.apply_config(self.mode_manager.config().clone()),
+            "spell_next" => self.goto_misspelled(false),
+            "spell_prev" => self.goto_misspelled(true),
+            "spell_suggest" => self.show_spelling_picker(),
+            "close_buffer" => self.close_current_buffer(false)?,
+            "force_close_buffer" => self.close_current_buffer(true)?,
+            "mksession" => match self.save_session() {
+                Ok(()) => self.mode_manager.show_message("Session saved"),
+                Err(e) => self.mode_manager.show_message(format!("E190: Cannot save session: {}", e)),
+            },
+            action if action.starts_with("terminal ") => self.start_terminal(&action["terminal ".len()..]),
+            action if action.starts_with("resize_explorer ") => {
+                self.resize_explorer(&action["resize_explorer ".len()..]);
+            }
+            action if action.starts_with("open ") => {
+                // A recent file picked on the dashboard
+                self.mode_manager.open_file(Path::new(&action["open ".len()..]), &mut self.buffer_manager);
+            }
+            _ => {}
+        }
+        Ok(false)
+    }
+    
+    /// Close the current buffer and switch to the previous one. Refuses to
+    /// drop unsaved changes unless `force` is set. Closing the last buffer
+    /// brings up the dashboard.
+    fn close_current_buffer(&mut self, force: bool) -> Result<()> {
+        let Some(id) = self.buffer_manager.current_buffer_id() else {
+            return Ok(());
+        };
+        if let Err(e) = self.buffer_manager.close_buffer(id, force) {
+            self.mode_manager.show_message(e.to_string());
+            return Ok(());
+        }
+        
+        if self.buffer_manager.is_empty() {
+            self.buffer_manager.create_buffer("untitled".to_string());
+            self.dashboard = Some(self.new_dashboard());
+        }
+        Ok(())
+    }
+    
+    // Ctrl-D in the buffer picker. Unsaved buffers stay open.
+    async fn delete_buffer_from_picker(&mut self, buffer_id: usize) -> Result<()> {
+        if let Err(e) = self.buffer_manager.close_buffer(buffer_id, false) {
+            if let Some(ref mut picker) = self.picker {
+                picker.set_notice(e.to_string());
+            }
+            return Ok(());
+        }
+        if self.buffer_manager.is_empty() {
+            self.buffer_manager.create_buffer("untitled".to_string());
+        }
+        if let Some(ref mut picker) = self.picker {
+            picker.refresh_buffers(&self.config, &self.buffer_manager).await?;
+        }
+        Ok(())
+    }
+    
+    // Give the explorer `change` more columns (`+N` / `-N`), or its usual
+    // width back with `=`, leaving the editor at least half the screen
+    fn resize_explorer(&mut self, change: &str) {
+        let Some(explorer) = self.explorer.as_mut() else {
+            return;
+        };
+        let max = self.terminal.size().map_or(u16::MAX, |size| size.width / 2).max(explorer::MIN_WIDTH);
+        explorer.width = match change.parse::<i32>() {
+            Ok(by) => (i32::from(explorer.width) + by).clamp(i32::from(explorer::MIN_WIDTH), i32::from(max)) as u16,
+            Err(_) => explorer::DEFAULT_WIDTH,
+        };
+    }
+    
+    // The explorer if it is on screen. A terminal too narrow for it hides
+    // it without closing it.
+    fn shown_explorer(&mut self) -> Option<&mut Explorer> {
+        let width = self.terminal.size().map_or(0, |size| size.width);
+        self.explorer.as_mut().filter(|explorer| explorer.shown_width(width).is_some())
+    }
+    
+    fn toggle_explorer(&mut self) {
+        match self.explorer {
+            Some(ref explorer) if explorer.focused => self.explorer = None,
+            Some(ref mut explorer) => explorer.focused = true,
+            None => {
+                let root = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+                self.explorer = Some(Explorer::new(root));
+            }
+        }
+    }
+    
     async fn handle_leader_key(&mut self) -> Result<bool> {
         // Wait for next key within timeout
-        if event::poll(Duration::from_millis(1000))? {
+        if event::poll(Duration::from_millis(self.config.keymaps.timeout_ms))? {
             if let Event::Key(key) = event::read()? {
                 match key.code {
                     KeyCode::Char('f') => {
@@ -220,9 +761,14 @@ impl App {
                         // Buffers
                         self.show_buffer_picker().await?;
                     }
+                    KeyCode::Char('p') | KeyCode::Char('P') => {
+                        // Put the system clipboard after / before the cursor
+                        let before = key.code == KeyCode::Char('P');
+                        self.mode_manager.paste_clipboard(before, &mut self.buffer_manager);
+                    }
                     KeyCode::Char('s') => {
                         // Resume session
-                        self.buffer_manager.resume_session()?;
+                        self.resume_session()?;
                     }
                     KeyCode::Char('r') => {
                         // Rename file
@@ -230,9 +776,33 @@ impl App {
                     }
                     KeyCode::Char('d') => {
                         // Show dashboard
-                        self.dashboard = Some(Dashboard::new(&self.config));
+                        self.dashboard = Some(self.new_dashboard());
+                    }
+                    KeyCode::Char('e') => {
+                        // Show and focus the file explorer, or hide it once focused
+                        self.toggle_explorer();
+                    }
+                    KeyCode::Char('l') => {
+                        // Lines of the current buffer
+                        self.show_buffer_lines_picker().await?;
+                    }
+                    KeyCode::Char('o') => {
+                        // Definitions and headings of the current buffer
+                        self.show_outline_picker().await?;
+                    }
+                    KeyCode::Char('W') => {
+                        // Word frequencies of the current buffer
+                        self.show_word_frequency_picker();
+                    }
+                    KeyCode::Char('z') => {
+                        // Toggle zen mode
+                        self.zen_mode_active = !self.zen_mode_active;
                     }
                     KeyCode::Char('q') => {
+                        // Close the buffer, not the editor
+                        self.close_current_buffer(false)?;
+                    }
+                    KeyCode::Char('Q') => {
                         return Ok(true); // Quit
                     }
                     _ => {}
@@ -245,19 +815,145 @@ impl App {
 
     
     async fn show_file_picker(&mut self) -> Result<()> {
-        self.picker = Some(Picker::new_file_picker(&self.config).await?);
-        Ok(())
+        let picker = Picker::new_file_picker(&self.config, None).await?;
+        self.open_picker(picker).await
     }
     
     async fn show_grep_picker(&mut self) -> Result<()> {
-        self.picker = Some(Picker::new_grep_picker(&self.config).await?);
-        Ok(())
+        let picker = Picker::new_grep_picker(&self.config).await?;
+        self.open_picker(picker).await
     }
     
     async fn show_buffer_picker(&mut self) -> Result<()> {
-        self.picker = Some(Picker::new_buffer_picker(&self.config, &self.buffer_manager).await?);
+        let picker = Picker::new_buffer_picker(&self.config, &self.buffer_manager).await?;
+        self.open_picker(picker).await
+    }
+    
+    // Show `picker`, prefilled with its remembered query if `remember_query` is on
+    async fn open_picker(&mut self, picker: Picker) -> Result<()> {
+        let history = self.picker_history.get(picker.picker_type().title());
+        let picker = picker.with_history(history).await?;
+        let query = self
+            .picker_queries
+            .get(picker.picker_type().title())
+            .filter(|_| self.config.picker.remember_query)
+            .cloned();
+        self.picker = Some(match query {
+            Some(query) => picker.with_query(query).await?,
+            None => picker,
+        });
         Ok(())
     }
+    
+    // Store the open picker's query for next time, unless closing it with
+    // `closed_by` is configured to forget it
+    fn remember_picker_query(&mut self, closed_by: ForgetQuery) {
+        if let Some(ref picker) = self.picker {
+            let title = picker.picker_type().title();
+            if self.config.picker.forget_query_on == closed_by {
+                self.picker_queries.remove(title);
+            } else {
+                self.picker_queries.insert(title, picker.query().to_string());
+            }
+        }
+    }
+    
+    // Add the open picker's query to its history and save it. An unsaved
+    // history only costs the recent searches, so failures aren't reported.
+    fn record_picker_history(&mut self) {
+        if let Some(picker) = self.picker.as_ref().filter(|picker| !picker.query().is_empty()) {
+            self.picker_history.push(picker.picker_type().title(), picker.query());
+            let _ = self.picker_history.save();
+        }
+    }
+    
+    fn show_help(&mut self) {
+        self.picker = Some(Picker::new_help_picker());
+    }
+    
+    fn show_messages(&mut self) {
+        self.picker = Some(Picker::new_messages_picker(self.mode_manager.message_history()));
+    }
+    
+    async fn show_buffer_lines_picker(&mut self) -> Result<()> {
+        if let Some(buffer) = self.buffer_manager.current_buffer() {
+            let picker = Picker::new_buffer_lines_picker(buffer);
+            self.open_picker(picker).await?;
+        }
+        Ok(())
+    }
+    
+    async fn show_outline_picker(&mut self) -> Result<()> {
+        if let Some(buffer) = self.buffer_manager.current_buffer() {
+            let picker = Picker::new_outline_picker(buffer);
+            self.open_picker(picker).await?;
+        }
+        Ok(())
+    }
+    
+    // `]s` / `[s`: move to the next / previous misspelled word
+    fn goto_misspelled(&mut self, backward: bool) {
+        let Some(spell) = self.ui.spell_checker() else {
+            self.mode_manager.show_message("E756: Spell checking is not enabled");
+            return;
+        };
+        let Some(buffer) = self.buffer_manager.current_buffer_mut() else {
+            return;
+        };
+        match spell.next_misspelled(&buffer.content, buffer.cursor.position(), backward) {
+            Some((pos, wrapped)) => {
+                buffer.cursor.move_to_position(pos);
+                if wrapped {
+                    let message = if backward {
+                        "search hit TOP, continuing at BOTTOM"
+                    } else {
+                        "search hit BOTTOM, continuing at TOP"
+                    };
+                    self.mode_manager.show_message(message);
+                }
+            }
+            None => self.mode_manager.show_message("No misspelled words"),
+        }
+    }
+    
+    // `z=`: list corrections for the word under the cursor
+    fn show_spelling_picker(&mut self) {
+        let Some(spell) = self.ui.spell_checker() else {
+            self.mode_manager.show_message("E756: Spell checking is not enabled");
+            return;
+        };
+        let iskeyword: Vec<char> = self.config.ui.iskeyword.chars().collect();
+        let Some((word, _, _)) = self
+            .buffer_manager
+            .current_buffer()
+            .and_then(|buffer| buffer.get_word_at(buffer.cursor.position(), &iskeyword))
+        else {
+            return;
+        };
+        let suggestions = spell.suggestions(&word);
+        if suggestions.is_empty() {
+            self.mode_manager.show_message("Sorry, no suggestions");
+        } else {
+            self.picker = Some(Picker::new_spelling_picker(&word, suggestions));
+        }
+    }
+    
+    // A correction picked after `z=`
+    fn replace_word_under_cursor(&mut self, replacement: &str) {
+        let iskeyword: Vec<char> = self.config.ui.iskeyword.chars().collect();
+        if let Some(buffer) = self.buffer_manager.current_buffer_mut() {
+            if let Some((_, start, end)) = buffer.get_word_at(buffer.cursor.position(), &iskeyword) {
+                buffer.replace_range(start, end, replacement);
+                buffer.cursor.move_to_position(start);
+            }
+        }
+    }
+    
+    fn show_word_frequency_picker(&mut self) {
+        if let Some(buffer) = self.buffer_manager.current_buffer() {
+            self.picker = Some(Picker::new_word_frequency_picker(buffer));
+        }
+    }
 }
 
 impl Drop for App {
@@ -266,7 +962,54 @@ impl Drop for App {
         let _ = execute!(
             self.terminal.backend_mut(),
             LeaveAlternateScreen,
-            DisableMouseCapture
+            DisableMouseCapture,
+            DisableBracketedPaste,
+            SetCursorStyle::DefaultUserShape
         );
     }
-} 
\ No newline at end of file
+}
+
+// The project name declared in the manifest in `root`, if any
+fn project_name(root: &Path) -> Option<String> {
+    let read = |file: &str| std::fs::read_to_string(root.join(file)).ok();
+    
+    if let Some(cargo) = read("Cargo.toml").and_then(|text| text.parse::<toml::Table>().ok()) {
+        return cargo.get("package")?.get("name")?.as_str().map(str::to_string);
+    }
+    if let Some(package) = read("package.json").and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok()) {
+        return package.get("name")?.as_str().map(str::to_string);
+    }
+    if let Some(pyproject) = read("pyproject.toml").and_then(|text| text.parse::<toml::Table>().ok()) {
+        let project = pyproject.get("project").or_else(|| pyproject.get("tool")?.get("poetry"))?;
+        return project.get("name")?.as_str().map(str::to_string);
+    }
+    if let Some(go_mod) = read("go.mod") {
+        // `module github.com/user/name` is named after its last path segment
+        let module = go_mod.lines().find_map(|line| line.strip_prefix("module "))?;
+        return module.trim().rsplit('/').next().map(str::to_string);
+    }
+    None
+}
+
+// Whether `a` and `b` name the same file, however they are written
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Take the vi-style `+{command}` arguments out of `files` as ex commands:
+/// `+42` goes to line 42, `+/pattern` searches and a bare `+` goes to the
+/// last line
+pub fn take_plus_commands(files: &mut Vec<PathBuf>) -> Vec<String> {
+    let mut commands = Vec::new();
+    files.retain(|file| match file.to_str().and_then(|arg| arg.strip_prefix('+')) {
+        Some(command) => {
+            commands.push(if command.is_empty() { "$".to_string() } else { command.to_string() });
+            false
+        }
+        None => true,
+    });
+    commands
+}
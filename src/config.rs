@@ -1,110 +1,362 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// Every section falls back to its defaults field by field, so config files
+// written by older versions keep loading as new options are added.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub ui: UIConfig,
+    pub editor: EditorConfig,
     pub keymaps: KeymapConfig,
     pub picker: PickerConfig,
     pub dashboard: DashboardConfig,
+    pub session: SessionConfig,
+    pub lsp: LspConfig,
+    /// The command `=` formats lines with, by filetype, e.g.
+    /// `rust = "rustfmt --edition 2021"`. It reads the lines on stdin and
+    /// prints them formatted.
+    pub formatters: HashMap<String, String>,
+    #[serde(rename = "autocmd")]
+    pub autocmds: Vec<AutocmdConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct UIConfig {
     pub theme: String,
     pub show_line_numbers: bool,
     pub show_status_line: bool,
-    pub tab_width: usize,
+    /// Characters besides letters and digits that words are made of, for
+    /// `*` and `#`
+    pub iskeyword: String,
+    /// Soft-wrap long lines; otherwise they're cut off at the edge and the
+    /// view scrolls sideways to follow the cursor
     pub wrap_lines: bool,
+    /// Show tabs as `>`, trailing spaces as `-` and non-breaking spaces as `+`
+    pub list: bool,
+    /// Underline words missing from `spell_file`
+    pub spell: bool,
+    /// The word list for `spell`, one word per line
+    pub spell_file: String,
+    /// Take insert mode keys as they come, for pasting into a terminal
+    /// without bracketed paste: no insert mode mappings, and Tab inserts a tab
+    pub paste: bool,
+    /// Start the rows of a wrapped line under its text rather than at the
+    /// left edge
+    pub break_indent: bool,
+    /// Columns wrapped rows start further in than the line's own indent
+    pub break_indent_shift: usize,
+    /// Scroll the line a search or jump (`n`, `*`, `G`, ...) lands on to the
+    /// middle of the screen
+    pub center_on_jump: bool,
+    pub zen_mode: bool,
+    /// Files bigger than this open without syntax highlighting or undo
+    pub large_file_threshold_bytes: u64,
+    pub startup_screen: StartupScreen,
+    /// Show inlay hints (parameter names, types) inside lines
+    pub inlay_hints: bool,
+    /// Apply the config file as soon as it is written from the editor
+    pub reload_on_write: bool,
+    /// Highlight what a `:s` command would replace while it is typed
+    pub inccommand: bool,
+}
+
+/// How text is indented and laid out as it is typed and saved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EditorConfig {
+    pub tab_width: usize,
+    /// Tab inserts spaces up to the next multiple of `tab_width`
+    pub expandtab: bool,
+    /// Columns the Tab key moves to the next multiple of; 0 is the indent
+    /// width (`tab_width` or the file's own)
+    pub softtabstop: usize,
+    /// Take `tab_width` and `expandtab` from the indentation of each file
+    pub detect_indent: bool,
+    pub trim_trailing_whitespace: bool,
+    pub insert_final_newline: bool,
+}
+
+/// What the editor shows when started without files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StartupScreen {
+    #[default]
+    Dashboard,
+    /// An empty buffer
+    Empty,
+    /// The buffers of the last session
+    LastSession,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct KeymapConfig {
     pub leader: String,
     pub timeout_ms: u64,
+    /// Mappings, each written as the `:map` command that makes it, e.g.
+    /// `"inoremap jk <Esc>"`
+    pub maps: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PickerConfig {
     pub file_ignore_patterns: Vec<String>,
     pub max_results: usize,
     pub preview_enabled: bool,
+    pub grep_regex: bool,
+    /// Files bigger than this are left out of the file picker and refused by `:e`
+    pub max_file_size_bytes: u64,
+    pub root: PickerRoot,
+    /// Prefix file entries with Nerd Font icons
+    pub show_icons: bool,
+    /// Reopen pickers with the query they were closed with
+    pub remember_query: bool,
+    pub forget_query_on: ForgetQuery,
+    /// List dotfiles in the file picker
+    pub show_hidden: bool,
+    /// Leave out what `.gitignore`, `.git/info/exclude` and the global
+    /// excludes file ignore
+    pub respect_gitignore: bool,
+    /// List directories in the file picker; Enter on one lists what is inside
+    pub show_directories: bool,
+    /// Inside a git work tree, list files with `git ls-files` instead of
+    /// walking the directories
+    pub use_git_files: bool,
+    /// With `use_git_files`, list untracked files that aren't ignored too
+    pub include_untracked: bool,
+}
+
+/// Which way of closing a picker drops the remembered query
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ForgetQuery {
+    /// Keep it either way
+    #[default]
+    Never,
+    /// Cancelling with Esc
+    Esc,
+    /// Choosing an entry with Enter
+    Enter,
+}
+
+/// Where the file picker starts walking
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PickerRoot {
+    /// The working directory
+    #[default]
+    Cwd,
+    /// The top of the git repository around the working directory, or the
+    /// working directory outside one
+    Git,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct DashboardConfig {
     pub show_recent_files: bool,
     pub max_recent_files: usize,
     pub custom_header: Option<String>,
 }
 
-impl Default for Config {
+/// What a session records, like Vim's `sessionoptions`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    /// Save the session when the editor quits, not only on `:mksession`
+    pub auto_save: bool,
+    pub save_cursor: bool,
+    /// Record the working directory
+    pub save_directory: bool,
+    /// Record which buffers had unsaved changes
+    pub save_modified: bool,
+    /// Record the `zf` folds and which folds were closed
+    pub save_folds: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LspConfig {
+    /// Start the configured language servers as their files are opened
+    pub enabled: bool,
+    #[serde(rename = "server")]
+    pub servers: Vec<LspServerConfig>,
+}
+
+/// A language server for files with one of `extensions`, e.g.
+/// `{ language = "rust", command = "rust-analyzer", extensions = ["rs"] }`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LspServerConfig {
+    /// The `languageId` the server is told files are in
+    pub language: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub extensions: Vec<String>,
+}
+
+/// Runs an ex command when `event` fires for a buffer whose file matches `pattern`,
+/// e.g. `{ event = "BufWritePre", pattern = "*.rs", command = '%s/\s\+$//e' }`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutocmdConfig {
+    pub event: String,
+    pub pattern: String,
+    pub command: String,
+}
+
+impl Default for UIConfig {
+    fn default() -> Self {
+        Self {
+            theme: "zen".to_string(),
+            show_line_numbers: false,
+            show_status_line: false,
+            iskeyword: "_".to_string(),
+            wrap_lines: false,
+            list: false,
+            spell: false,
+            paste: false,
+            spell_file: "/usr/share/dict/words".to_string(),
+            break_indent: false,
+            break_indent_shift: 0,
+            center_on_jump: false,
+            zen_mode: false,
+            large_file_threshold_bytes: 1024 * 1024,
+            startup_screen: StartupScreen::default(),
+            inlay_hints: true,
+            reload_on_write: true,
+            inccommand: true,
+        }
+    }
+}
+
+impl Default for EditorConfig {
     fn default() -> Self {
         Self {
-            ui: UIConfig {
-                theme: "zen".to_string(),
-                show_line_numbers: false,
-                show_status_line: false,
-                tab_width: 2,
-                wrap_lines: false,
-            },
-            keymaps: KeymapConfig {
-                leader: " ".to_string(),
-                timeout_ms: 1000,
-            },
-            picker: PickerConfig {
-                file_ignore_patterns: vec![
-                    ".git".to_string(),
-                    "node_modules".to_string(),
-                    "target".to_string(),
-                    "*.pyc".to_string(),
-                ],
-                max_results: 100,
-                preview_enabled: true,
-            },
-            dashboard: DashboardConfig {
-                show_recent_files: true,
-                max_recent_files: 5,
-                custom_header: None,
-            },
+            tab_width: 2,
+            expandtab: false,
+            softtabstop: 0,
+            detect_indent: true,
+            trim_trailing_whitespace: false,
+            insert_final_newline: true,
+        }
+    }
+}
+
+impl Default for KeymapConfig {
+    fn default() -> Self {
+        Self {
+            leader: " ".to_string(),
+            timeout_ms: 1000,
+            maps: Vec::new(),
+        }
+    }
+}
+
+impl Default for PickerConfig {
+    fn default() -> Self {
+        Self {
+            file_ignore_patterns: vec![
+                ".git".to_string(),
+                "node_modules".to_string(),
+                "target".to_string(),
+                "*.pyc".to_string(),
+            ],
+            max_results: 100,
+            preview_enabled: true,
+            grep_regex: false,
+            max_file_size_bytes: 10 * 1024 * 1024,
+            root: PickerRoot::default(),
+            show_icons: false,
+            remember_query: false,
+            forget_query_on: ForgetQuery::default(),
+            show_hidden: false,
+            respect_gitignore: true,
+            show_directories: false,
+            use_git_files: true,
+            include_untracked: true,
+        }
+    }
+}
+
+impl Default for DashboardConfig {
+    fn default() -> Self {
+        Self {
+            show_recent_files: true,
+            max_recent_files: 5,
+            custom_header: None,
+        }
+    }
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            auto_save: true,
+            save_cursor: true,
+            save_directory: true,
+            save_modified: true,
+            save_folds: true,
+        }
+    }
+}
+
+impl Default for LspConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            servers: Vec::new(),
         }
     }
 }
 
 impl Config {
+    /// The config without what it scripts, its autocommands and mappings,
+    /// as `--noplugin` asks
+    pub fn without_plugins(mut self) -> Self {
+        self.autocmds.clear();
+        self.keymaps.maps.clear();
+        self
+    }
+    
+    /// Where `load` reads the config from when given `config_path`
+    pub fn file_path(config_path: Option<PathBuf>) -> PathBuf {
+        config_path.unwrap_or_else(config_dir).join("config.toml")
+    }
+    
     pub fn load(config_path: Option<PathBuf>) -> Result<Self> {
         let config_dir = match config_path {
             Some(path) => path,
-            None => {
-                let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-                PathBuf::from(home).join(".config").join("zen-vim")
-            }
+            None => config_dir(),
         };
         
+        let config_file = Self::create_if_missing(&config_dir)?;
+        let content = std::fs::read_to_string(config_file)?;
+        let mut value: toml::Value = toml::from_str(&content)?;
+        move_editor_options(&mut value);
+        Ok(value.try_into()?)
+    }
+    
+    /// Write the commented default config to `config_dir/config.toml` unless
+    /// the file already exists. Returns the path of the config file.
+    pub fn create_if_missing(config_dir: &Path) -> Result<PathBuf> {
         let config_file = config_dir.join("config.toml");
-        
-        if config_file.exists() {
-            let content = std::fs::read_to_string(config_file)?;
-            let config: Config = toml::from_str(&content)?;
-            Ok(config)
-        } else {
-            // Create default config file
-            let default_config = Config::default();
-            std::fs::create_dir_all(&config_dir)?;
-            let toml_content = toml::to_string_pretty(&default_config)?;
-            std::fs::write(config_file, toml_content)?;
-            Ok(default_config)
+        if !config_file.exists() {
+            std::fs::create_dir_all(config_dir)?;
+            std::fs::write(&config_file, generate_default_config_file())?;
         }
+        Ok(config_file)
     }
     
+    #[allow(dead_code)]
     pub fn save(&self, config_path: Option<PathBuf>) -> Result<()> {
         let config_dir = match config_path {
             Some(path) => path,
-            None => {
-                let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-                PathBuf::from(home).join(".config").join("zen-vim")
-            }
+            None => config_dir(),
         };
         
         std::fs::create_dir_all(&config_dir)?;
@@ -113,4 +365,257 @@ impl Config {
         std::fs::write(config_file, toml_content)?;
         Ok(())
     }
-} 
\ No newline at end of file
+}
+
+// The `[editor]` options used to be in `[ui]`. Files still setting them
+// there keep working, unless `[editor]` sets the same option.
+fn move_editor_options(config: &mut toml::Value) {
+    const MOVED: &[&str] =
+        &["tab_width", "expandtab", "softtabstop", "detect_indent", "trim_trailing_whitespace", "insert_final_newline"];
+    let Some(table) = config.as_table_mut() else {
+        return;
+    };
+    let Some(ui) = table.get_mut("ui").and_then(toml::Value::as_table_mut) else {
+        return;
+    };
+    let moved: Vec<(String, toml::Value)> =
+        MOVED.iter().filter_map(|&key| ui.remove(key).map(|value| (key.to_string(), value))).collect();
+    if moved.is_empty() {
+        return;
+    }
+    let editor = table.entry("editor").or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    if let Some(editor) = editor.as_table_mut() {
+        for (key, value) in moved {
+            editor.entry(key).or_insert(value);
+        }
+    }
+}
+
+/// `~/.config/zen-vim`, where the config and other saved state live
+pub fn config_dir() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+    PathBuf::from(home).join(".config").join("zen-vim")
+}
+
+/// The default config as TOML, with a comment documenting every option.
+/// `toml` can't emit comments, so the file is written out by hand; the values
+/// come from the `Default` impls so the two can't drift apart.
+pub fn generate_default_config_file() -> String {
+    let ui = UIConfig::default();
+    let editor = EditorConfig::default();
+    let keymaps = KeymapConfig::default();
+    let picker = PickerConfig::default();
+    let dashboard = DashboardConfig::default();
+    let session = SessionConfig::default();
+    let lsp = LspConfig::default();
+    
+    format!(
+        r#"# zen-vim configuration
+# Every option is optional; anything left out falls back to the default shown.
+
+[ui]
+# Color theme (default: {theme})
+theme = {theme}
+# Show line numbers in the gutter (default: {show_line_numbers})
+show_line_numbers = {show_line_numbers}
+# Show the status line at the bottom (default: {show_status_line})
+show_status_line = {show_status_line}
+# Characters besides letters and digits that * and # take as part of a word (default: {iskeyword})
+iskeyword = {iskeyword}
+# Soft-wrap lines longer than the window, or cut them off and scroll
+# sideways (default: {wrap_lines})
+wrap_lines = {wrap_lines}
+# Show tabs as >, trailing spaces as - and non-breaking spaces as +; :set list
+# toggles it (default: {list})
+list = {list}
+# Underline words missing from spell_file; :set spell toggles it (default: {spell})
+spell = {spell}
+# Word list for spell checking, one word per line (default: {spell_file})
+spell_file = {spell_file}
+# Insert mode ignores mappings and Tab inserts a tab, so text pasted without
+# bracketed paste comes in as it is; :set paste toggles it (default: {paste})
+paste = {paste}
+# Indent the rows of a wrapped line to where its text starts (default: {break_indent})
+break_indent = {break_indent}
+# With break_indent, indent wrapped rows this many more columns (default: {break_indent_shift})
+break_indent_shift = {break_indent_shift}
+# Scroll the line that n, *, G and other jumps land on to the middle of the
+# screen (default: {center_on_jump})
+center_on_jump = {center_on_jump}
+# Start in zen mode, hiding everything but the text (default: {zen_mode})
+zen_mode = {zen_mode}
+# Files bigger than this, in bytes, open without syntax highlighting, undo or folds (default: {large_file_threshold_bytes})
+large_file_threshold_bytes = {large_file_threshold_bytes}
+# What to show when started without files: "dashboard", "empty" or
+# "last_session" (default: {startup_screen})
+startup_screen = {startup_screen}
+# Show inlay hints like parameter names and types inside lines (default: {inlay_hints})
+inlay_hints = {inlay_hints}
+# Apply this file as soon as it is written from zen-vim (default: {reload_on_write})
+reload_on_write = {reload_on_write}
+# Highlight the matches of a :s command while it is typed (default: {inccommand})
+inccommand = {inccommand}
+
+[editor]
+# Number of spaces per tab (default: {tab_width})
+tab_width = {tab_width}
+# Insert spaces instead of a tab character (default: {expandtab})
+expandtab = {expandtab}
+# The Tab key fills up to the next multiple of this many columns, using tabs
+# where they fit unless expandtab is set; 0 means the indent width (default: {softtabstop})
+softtabstop = {softtabstop}
+# Follow the indentation of each file instead of the two settings above,
+# shown in the status line as tab:N or spc:N (default: {detect_indent})
+detect_indent = {detect_indent}
+# Strip trailing whitespace from every line when saving (default: {trim_trailing_whitespace})
+trim_trailing_whitespace = {trim_trailing_whitespace}
+# Make sure saved files end with a newline (default: {insert_final_newline})
+insert_final_newline = {insert_final_newline}
+
+[keymaps]
+# Leader key for <space> commands (default: {leader})
+leader = {leader}
+# How long to wait for the rest of a key sequence, in milliseconds (default: {timeout_ms})
+timeout_ms = {timeout_ms}
+# Mappings, each written as the :map command that makes it
+# maps = ["inoremap jk <Esc>", "nnoremap <C-s> :w<CR>"]
+
+[picker]
+# Files and directories the file picker skips
+file_ignore_patterns = {file_ignore_patterns}
+# Maximum number of files or grep matches to list (default: {max_results})
+max_results = {max_results}
+# Show a preview of the selected item (default: {preview_enabled})
+preview_enabled = {preview_enabled}
+# Start the grep picker in regex mode instead of literal (default: {grep_regex})
+grep_regex = {grep_regex}
+# Files bigger than this, in bytes, are not listed or opened (default: {max_file_size_bytes})
+max_file_size_bytes = {max_file_size_bytes}
+# Where the file picker searches: "cwd", or "git" for the top of the git
+# repository (default: {root})
+root = {root}
+# Show file type icons; needs a Nerd Font (default: {show_icons})
+show_icons = {show_icons}
+# Reopen each picker with its last query (default: {remember_query})
+remember_query = {remember_query}
+# When to drop the remembered query: "never", "esc" or "enter" (default: {forget_query_on})
+forget_query_on = {forget_query_on}
+# List hidden files in the file picker; Ctrl-H toggles it (default: {show_hidden})
+show_hidden = {show_hidden}
+# Leave out files ignored by .gitignore or .git/info/exclude; Ctrl-G toggles it (default: {respect_gitignore})
+respect_gitignore = {respect_gitignore}
+# List directories too; Enter on one lists its contents and Backspace after
+# a / goes back up (default: {show_directories})
+show_directories = {show_directories}
+# In a git repository, ask git for the files instead of walking the
+# directories, which is much faster in big repositories (default: {use_git_files})
+use_git_files = {use_git_files}
+# With use_git_files, list untracked files that aren't ignored too (default: {include_untracked})
+include_untracked = {include_untracked}
+
+[dashboard]
+# List recently opened files (default: {show_recent_files})
+show_recent_files = {show_recent_files}
+# How many recent files to list (default: {max_recent_files})
+max_recent_files = {max_recent_files}
+# Replace the ASCII art header with your own text
+# custom_header = "Welcome back"
+
+[session]
+# Save the session when quitting; :mksession saves it any time (default: {auto_save})
+auto_save = {auto_save}
+# Remember where the cursor was in each file (default: {save_cursor})
+save_cursor = {save_cursor}
+# Remember the working directory (default: {save_directory})
+save_directory = {save_directory}
+# Remember which buffers had unsaved changes (default: {save_modified})
+save_modified = {save_modified}
+# Remember manual folds and which folds were closed (default: {save_folds})
+save_folds = {save_folds}
+
+[lsp]
+# Start the language servers below as their files are opened (default: {lsp_enabled})
+enabled = {lsp_enabled}
+
+# A language server for files with these extensions. Its errors and warnings
+# are marked next to the line numbers and underlined in the text.
+# [[lsp.server]]
+# language = "rust"
+# command = "rust-analyzer"
+# args = []
+# extensions = ["rs"]
+
+# The commands the `=` operator formats lines with, by filetype (rust,
+# python, javascript, typescript, go, c, cpp, ...). They read the lines on
+# stdin and print them formatted; if one fails the lines are left as they were.
+# [formatters]
+# rust = "rustfmt --edition 2021"
+# python = "black -q -"
+# typescript = "prettier --stdin-filepath file.ts"
+
+# Per-filetype settings aren't read yet; this is what they will look like.
+# [filetypes.rust]
+# tab_width = 4
+
+# Autocommands run an ex command on buffer events (BufReadPost, BufWritePre,
+# BufWritePost) for files matching a glob pattern.
+# [[autocmd]]
+# event = "BufWritePre"
+# pattern = "*.rs"
+# command = '%s/\s\+$//e'
+"#,
+        theme = toml_value(&ui.theme),
+        show_line_numbers = ui.show_line_numbers,
+        show_status_line = ui.show_status_line,
+        iskeyword = toml_value(&ui.iskeyword),
+        wrap_lines = ui.wrap_lines,
+        list = ui.list,
+        spell = ui.spell,
+        paste = ui.paste,
+        spell_file = toml_value(&ui.spell_file),
+        break_indent = ui.break_indent,
+        break_indent_shift = ui.break_indent_shift,
+        center_on_jump = ui.center_on_jump,
+        zen_mode = ui.zen_mode,
+        large_file_threshold_bytes = ui.large_file_threshold_bytes,
+        startup_screen = toml_value(&ui.startup_screen),
+        inlay_hints = ui.inlay_hints,
+        reload_on_write = ui.reload_on_write,
+        inccommand = ui.inccommand,
+        tab_width = editor.tab_width,
+        expandtab = editor.expandtab,
+        softtabstop = editor.softtabstop,
+        detect_indent = editor.detect_indent,
+        trim_trailing_whitespace = editor.trim_trailing_whitespace,
+        insert_final_newline = editor.insert_final_newline,
+        leader = toml_value(&keymaps.leader),
+        timeout_ms = keymaps.timeout_ms,
+        file_ignore_patterns = toml_value(&picker.file_ignore_patterns),
+        max_results = picker.max_results,
+        preview_enabled = picker.preview_enabled,
+        grep_regex = picker.grep_regex,
+        max_file_size_bytes = picker.max_file_size_bytes,
+        root = toml_value(&picker.root),
+        show_icons = picker.show_icons,
+        remember_query = picker.remember_query,
+        forget_query_on = toml_value(&picker.forget_query_on),
+        show_hidden = picker.show_hidden,
+        respect_gitignore = picker.respect_gitignore,
+        show_directories = picker.show_directories,
+        use_git_files = picker.use_git_files,
+        include_untracked = picker.include_untracked,
+        show_recent_files = dashboard.show_recent_files,
+        max_recent_files = dashboard.max_recent_files,
+        auto_save = session.auto_save,
+        save_cursor = session.save_cursor,
+        save_directory = session.save_directory,
+        save_modified = session.save_modified,
+        save_folds = session.save_folds,
+        lsp_enabled = lsp.enabled,
+    )
+}
+
+// `value` written as a TOML literal, e.g. `"zen"` or `[".git", "target"]`
+fn toml_value<T: Serialize>(value: &T) -> String {
+    toml::Value::try_from(value).map(|value| value.to_string()).unwrap_or_default()
+}
@@ -1,8 +1,21 @@
 use anyhow::{anyhow, Result};
+use regex::Regex;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use unicode_segmentation::UnicodeSegmentation;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Instant;
 
 use super::cursor::{Cursor, Position};
+use super::fold::{self, Fold, FoldCommand};
+use super::register::{Register, RegisterKind};
+use super::syntax::{self, Highlight, Language, SyntaxCache};
+use super::wrap;
+use crate::config::Config;
+
+/// Where each match of a search starts and (exclusively) ends
+pub type Matches = Rc<[(Position, Position)]>;
 
 #[derive(Debug, Clone)]
 pub struct Buffer {
@@ -12,8 +25,158 @@ pub struct Buffer {
     pub cursor: Cursor,
     pub modified: bool,
     pub name: String,
-    undo_stack: Vec<Vec<String>>,
-    redo_stack: Vec<Vec<String>>,
+    /// Whether the file on disk ended with a newline
+    pub final_newline: bool,
+    /// Set for files over `large_file_threshold_bytes`: no syntax
+    /// highlighting and no undo history
+    pub large_file_mode: bool,
+    pub language: Option<&'static Language>,
+    pub syntax: SyntaxCache,
+    /// First row changed since the syntax cache was last refreshed
+    pub syntax_dirty_from: Option<usize>,
+    syntax_dirty_to: usize,
+    // Content snapshots with where the cursor was when the change was made
+    undo_stack: Vec<(Vec<String>, Position)>,
+    redo_stack: Vec<(Vec<String>, Position)>,
+    /// Where recent edits happened, oldest first, for `g;` and `g,`
+    pub change_list: Vec<Position>,
+    /// Position in `change_list`; `change_list.len()` when not browsing it
+    pub change_index: usize,
+    /// Annotations from a language server, by row
+    pub inlay_hints: HashMap<usize, Vec<InlayHint>>,
+    /// Errors and warnings from a language server, in the order it sent them
+    pub diagnostics: Vec<Diagnostic>,
+    /// Goes up on every change to the content, like Vim's `b:changedtick`
+    pub changedtick: u64,
+    /// Folds from the indentation, worked out once a fold command is used,
+    /// and the ones made with `zf`
+    pub folds: Vec<Fold>,
+    // `changedtick` the folds were worked out at, `None` before any fold command
+    folds_tick: Option<u64>,
+    // Lines in the buffer and the first row changed since then, to move the
+    // manual folds along with the text
+    folds_len: usize,
+    folds_dirty_from: Option<usize>,
+    /// Indentation found in the file, used instead of `tab_width` and
+    /// `expandtab` for this buffer
+    pub indent: Option<DetectedIndent>,
+    /// When the buffer was last written, shown as "saved 3m ago"
+    pub last_saved: Option<Instant>,
+    /// Where insert mode was last left, for `gi`
+    pub last_insert_pos: Option<Position>,
+    /// Where the cursor was before the last jump, the `'` mark
+    pub jump_mark: Option<Position>,
+    /// First row on screen, kept between draws so the view only scrolls
+    /// once the cursor leaves it
+    pub top_line: Cell<usize>,
+    /// Cells per screen row the buffer was last drawn wrapped at, for `gj`
+    /// and `gk`; `None` while lines aren't wrapped
+    pub wrap_width: Cell<Option<usize>>,
+    /// Whether the inlay hints were drawn last time, so `gj` and `gk` count
+    /// their cells like the screen does
+    pub hints_shown: Cell<bool>,
+    /// First cell of each line on screen while lines aren't wrapped, kept
+    /// like `top_line` so the view only scrolls sideways to follow the cursor
+    pub left_col: Cell<usize>,
+    // `find_all_matches` of the last pattern asked for, at the `changedtick`
+    // it was found at
+    search_matches: RefCell<Option<(String, u64, Matches)>>,
+    /// Scroll the cursor line to the middle of the screen on the next draw
+    pub center_cursor: Cell<bool>,
+}
+
+/// Sizes of a stretch of text, as `g<C-G>` reports them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextStats {
+    pub lines: usize,
+    pub words: usize,
+    /// User-perceived characters (grapheme clusters)
+    pub chars: usize,
+    pub bytes: usize,
+}
+
+/// How a file is indented, as guessed by `Buffer::detect_indent`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetectedIndent {
+    pub tab_width: usize,
+    pub use_tabs: bool,
+}
+
+impl DetectedIndent {
+    /// The indent settings of the config
+    pub fn from_config(config: &Config) -> Self {
+        Self { tab_width: config.editor.tab_width, use_tabs: !config.editor.expandtab }
+    }
+}
+
+impl std::fmt::Display for DetectedIndent {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let style = if self.use_tabs { "tab" } else { "spc" };
+        write!(f, "{}:{}", style, self.tab_width)
+    }
+}
+
+/// Virtual text shown inside a line but not part of it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InlayHint {
+    /// Char column the hint sits at
+    pub col: usize,
+    pub label: String,
+    pub kind: InlayKind,
+}
+
+impl InlayHint {
+    /// The text the hint adds to its line
+    pub fn text(&self) -> String {
+        match self.kind {
+            InlayKind::Parameter => format!("{}: ", self.label),
+            InlayKind::Type => format!(": {}", self.label),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InlayKind {
+    /// A parameter name, shown before the argument as `name: `
+    Parameter,
+    /// An inferred type, shown after the binding as `: Type`
+    Type,
+}
+
+/// A problem a language server found in the text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub start: Position,
+    /// Just past the last character; the same as `start` for a point
+    pub end: Position,
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+// Edit sites kept in the change list
+const MAX_CHANGES: usize = 100;
+
+// Rows past the screen kept highlighted, so scrolling a little finds them ready
+const SYNTAX_MARGIN: usize = 100;
+
+/// The shape of a visual selection
+#[allow(dead_code)] // Only charwise selections have a key so far
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisualKind {
+    /// From the anchor to the cursor, as `v` selects
+    Char,
+    /// Whole lines, as `V` selects
+    Line,
+    /// The rectangle between anchor and cursor, as `Ctrl-V` selects
+    Block,
 }
 
 impl Buffer {
@@ -25,20 +188,57 @@ impl Buffer {
             cursor: Cursor::new(),
             modified: false,
             name,
+            final_newline: false,
+            large_file_mode: false,
+            language: None,
+            syntax: SyntaxCache::default(),
+            syntax_dirty_from: None,
+            syntax_dirty_to: 0,
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
+            change_list: Vec::new(),
+            change_index: 0,
+            inlay_hints: HashMap::new(),
+            diagnostics: Vec::new(),
+            changedtick: 0,
+            folds: Vec::new(),
+            folds_tick: None,
+            folds_len: 0,
+            folds_dirty_from: None,
+            indent: None,
+            last_saved: None,
+            last_insert_pos: None,
+            jump_mark: None,
+            top_line: Cell::new(0),
+            wrap_width: Cell::new(None),
+            hints_shown: Cell::new(false),
+            left_col: Cell::new(0),
+            search_matches: RefCell::new(None),
+            center_cursor: Cell::new(false),
         }
     }
     
-    pub fn from_file<P: AsRef<Path>>(id: usize, path: P) -> Result<Self> {
+    pub fn from_file<P: AsRef<Path>>(id: usize, path: P, config: &Config) -> Result<Self> {
         let path = path.as_ref().to_path_buf();
-        let content = if path.exists() {
-            std::fs::read_to_string(&path)?
-                .lines()
-                .map(|s| s.to_string())
-                .collect()
+        let size = path.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+        if size > config.picker.max_file_size_bytes {
+            return Err(anyhow!(
+                "File too large ({} bytes, max_file_size_bytes is {})",
+                size,
+                config.picker.max_file_size_bytes
+            ));
+        }
+        let large_file_mode = size > config.ui.large_file_threshold_bytes;
+        
+        let (content, final_newline) = if path.exists() {
+            let text = std::fs::read_to_string(&path)?;
+            let mut lines: Vec<String> = text.lines().map(|s| s.to_string()).collect();
+            if lines.is_empty() {
+                lines.push(String::new());
+            }
+            (lines, text.ends_with('\n'))
         } else {
-            vec![String::new()]
+            (vec![String::new()], false)
         };
         
         let name = path
@@ -49,44 +249,341 @@ impl Buffer {
             
         Ok(Self {
             id,
+            language: if large_file_mode { None } else { syntax::language_for(&path) },
             path: Some(path),
             content,
             cursor: Cursor::new(),
             modified: false,
             name,
+            final_newline,
+            large_file_mode,
+            syntax: SyntaxCache::default(),
+            syntax_dirty_from: None,
+            syntax_dirty_to: 0,
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
+            change_list: Vec::new(),
+            change_index: 0,
+            inlay_hints: HashMap::new(),
+            diagnostics: Vec::new(),
+            changedtick: 0,
+            folds: Vec::new(),
+            folds_tick: None,
+            folds_len: 0,
+            folds_dirty_from: None,
+            indent: None,
+            last_saved: None,
+            last_insert_pos: None,
+            jump_mark: None,
+            top_line: Cell::new(0),
+            wrap_width: Cell::new(None),
+            hints_shown: Cell::new(false),
+            left_col: Cell::new(0),
+            search_matches: RefCell::new(None),
+            center_cursor: Cell::new(false),
         })
     }
     
-    pub fn save(&mut self) -> Result<()> {
-        if let Some(ref path) = self.path {
-            let content = self.content.join("\n");
-            std::fs::write(path, content)?;
-            self.modified = false;
-            Ok(())
-        } else {
-            Err(anyhow!("No file path set"))
+    /// Guess the indentation from the lines that have some: tabs if more
+    /// lines start with a tab than with spaces, otherwise spaces in steps of
+    /// the largest width dividing every indent. A file can't tell how wide
+    /// its tabs are, so tab indents keep `tab_width`. None without enough
+    /// to go on.
+    pub fn detect_indent(&self, tab_width: usize) -> Option<DetectedIndent> {
+        let mut tab_lines = 0;
+        let mut space_lines = 0;
+        let mut width = 0;
+        for line in &self.content {
+            if line.starts_with('\t') {
+                tab_lines += 1;
+                continue;
+            }
+            let spaces = line.len() - line.trim_start_matches(' ').len();
+            // Blank lines and the ` * ` of block comments say nothing
+            if spaces == 0 || line.trim().is_empty() || line[spaces..].starts_with('*') {
+                continue;
+            }
+            space_lines += 1;
+            width = gcd(width, spaces);
         }
+        
+        if tab_lines > space_lines {
+            return Some(DetectedIndent { tab_width, use_tabs: true });
+        }
+        (2..=8).contains(&width).then_some(DetectedIndent { tab_width: width, use_tabs: false })
+    }
+    
+    pub fn save(&mut self, config: &Config) -> Result<()> {
+        let path = self.path.clone().ok_or_else(|| anyhow!("No file path set"))?;
+        self.prepare_for_save(config);
+        std::fs::write(path, self.contents_for_save(config))?;
+        self.modified = false;
+        self.last_saved = Some(Instant::now());
+        Ok(())
     }
     
-    pub fn save_as<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+    pub fn save_as<P: AsRef<Path>>(&mut self, path: P, config: &Config) -> Result<()> {
         let path = path.as_ref().to_path_buf();
-        let content = self.content.join("\n");
-        std::fs::write(&path, content)?;
+        self.prepare_for_save(config);
+        std::fs::write(&path, self.contents_for_save(config))?;
+        // The new name may mean a different language
+        if !self.large_file_mode {
+            self.language = syntax::language_for(&path);
+        }
+        self.syntax.clear();
         self.path = Some(path);
         self.modified = false;
+        self.last_saved = Some(Instant::now());
         Ok(())
     }
     
+    /// Append rows `start..=end` to the file at `path`, creating it if
+    /// needed (`:w >> file`). Returns the number of lines appended.
+    pub fn append_to(&self, path: &Path, start: usize, end: usize) -> Result<usize> {
+        let end = end.min(self.content.len().saturating_sub(1));
+        let mut text = String::new();
+        for line in self.content.get(start..=end).unwrap_or_default() {
+            text.push_str(line);
+            text.push('\n');
+        }
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+        std::io::Write::write_all(&mut file, text.as_bytes())?;
+        Ok(end + 1 - start.min(end + 1))
+    }
+    
+    // The text to write. `insert_final_newline` forces a trailing newline,
+    // otherwise the file keeps whatever it had when it was read.
+    fn contents_for_save(&self, config: &Config) -> String {
+        let mut text = self.content.join("\n");
+        let is_empty = self.content.len() == 1 && self.content[0].is_empty();
+        if (config.editor.insert_final_newline && !is_empty) || self.final_newline {
+            text.push('\n');
+        }
+        text
+    }
+    
+    // Apply the on-save cleanups enabled in `config` to the buffer itself
+    fn prepare_for_save(&mut self, config: &Config) {
+        if config.editor.trim_trailing_whitespace {
+            self.trim_trailing_whitespace();
+        }
+    }
+    
+    /// Strip trailing whitespace from every line as a single undo step.
+    /// The cursor stays put, clamped to its (possibly shorter) line.
+    pub fn trim_trailing_whitespace(&mut self) {
+        if !self.content.iter().any(|line| line.ends_with(char::is_whitespace)) {
+            return;
+        }
+        
+        self.push_undo();
+        for line in &mut self.content {
+            let trimmed_len = line.trim_end().len();
+            line.truncate(trimmed_len);
+        }
+        self.mark_all_syntax_dirty();
+        
+        let pos = self.cursor.position();
+        let line_len = self.content[pos.row].chars().count();
+        self.cursor.move_to_position(Position { row: pos.row, col: pos.col.min(line_len) });
+        self.modified = true;
+    }
+    
+    /// Fill up to the next multiple of `softtabstop` columns, with spaces
+    /// unless `use_tabs`. Then the whitespace before the cursor is rewritten
+    /// with as many `tabstop` wide tabs as fit, so two 4-column steps with
+    /// an 8-column tabstop make one tab.
+    pub fn insert_tab(&mut self, softtabstop: usize, tabstop: usize, use_tabs: bool) {
+        let pos = self.cursor.position();
+        let Some(line) = self.content.get(pos.row) else {
+            return;
+        };
+        if softtabstop == 0 || tabstop == 0 {
+            self.insert_char('\t');
+            return;
+        }
+        let before: Vec<char> = line.chars().take(pos.col).collect();
+        let column = display_width(&before, tabstop);
+        let target = (column / softtabstop + 1) * softtabstop;
+        
+        let (start, filled) = if use_tabs {
+            let run = before.iter().rev().take_while(|c| **c == ' ' || **c == '\t').count();
+            let start = pos.col - run;
+            let mut filled = String::new();
+            let mut column = display_width(&before[..start], tabstop);
+            while (column / tabstop + 1) * tabstop <= target {
+                filled.push('\t');
+                column = (column / tabstop + 1) * tabstop;
+            }
+            filled.push_str(&" ".repeat(target - column));
+            (start, filled)
+        } else {
+            (pos.col, " ".repeat(target - column))
+        };
+        self.replace_range(Position { row: pos.row, col: start }, pos, &filled);
+    }
+    
+    /// Shift rows `start..=end` right by `levels` steps of `indent`, or left
+    /// for negative `levels`, as one undo step. Blank lines stay empty. The
+    /// cursor keeps its place in the text, or lands on the first non-blank
+    /// if it was in the indent.
+    pub fn indent_lines(&mut self, start: usize, end: usize, levels: isize, indent: DetectedIndent) {
+        let end = end.min(self.content.len().saturating_sub(1));
+        if start > end || indent.tab_width == 0 {
+            return;
+        }
+        self.push_undo();
+        for row in start..=end {
+            if self.content[row].trim().is_empty() {
+                continue;
+            }
+            let width = indent_width(&self.content[row], indent.tab_width) as isize;
+            let shifted = (width + levels * indent.tab_width as isize).max(0) as usize;
+            self.set_indent(row, shifted, indent);
+        }
+        self.modified = true;
+    }
+    
+    /// Lay out the whitespace of rows `start..=end` again as one undo step:
+    /// tabs `tab_width` wide become spaces, or with `indent.use_tabs` the
+    /// indentation becomes tabs `indent.tab_width` wide. Whitespace keeps
+    /// the columns it reaches, so text lined up after it stays lined up.
+    pub fn retab(&mut self, start: usize, end: usize, tab_width: usize, indent: DetectedIndent) {
+        let end = end.min(self.content.len().saturating_sub(1));
+        if start > end || tab_width == 0 || indent.tab_width == 0 {
+            return;
+        }
+        let retabbed: Vec<(usize, String)> = (start..=end)
+            .filter_map(|row| {
+                let line = retab_line(&self.content[row], tab_width, indent);
+                (line != self.content[row]).then_some((row, line))
+            })
+            .collect();
+        if retabbed.is_empty() {
+            return;
+        }
+        self.push_undo();
+        for (row, line) in retabbed {
+            self.content[row] = line;
+            self.mark_syntax_dirty(row);
+        }
+        self.modified = true;
+        self.clamp_cursor();
+    }
+    
+    /// Drop the character before the cursor and all the indent of its line,
+    /// as one change
+    pub fn clear_indent_with_marker(&mut self) {
+        let pos = self.cursor.position();
+        if pos.col == 0 || pos.row >= self.content.len() {
+            return;
+        }
+        self.push_undo();
+        let line = &mut self.content[pos.row];
+        let start = char_to_byte(line, pos.col - 1);
+        let end = char_to_byte(line, pos.col);
+        line.drain(start..end);
+        self.cursor.move_to_position(Position { row: pos.row, col: pos.col - 1 });
+        self.set_indent(pos.row, 0, DetectedIndent { tab_width: 1, use_tabs: false });
+        self.modified = true;
+    }
+    
+    // Replace the leading whitespace of `row` with `width` columns of indent
+    fn set_indent(&mut self, row: usize, width: usize, indent: DetectedIndent) {
+        let line = &self.content[row];
+        let old_len = first_non_blank(line);
+        let leading = if indent.use_tabs {
+            format!("{}{}", "\t".repeat(width / indent.tab_width), " ".repeat(width % indent.tab_width))
+        } else {
+            " ".repeat(width)
+        };
+        let new_len = leading.chars().count();
+        self.content[row] = leading + &line[char_to_byte(line, old_len)..];
+        self.mark_syntax_dirty(row);
+        
+        let pos = self.cursor.position();
+        if pos.row == row {
+            let col = if pos.col >= old_len { pos.col - old_len + new_len } else { new_len };
+            self.cursor.move_to_position(Position { row, col });
+        }
+    }
+    
     fn push_undo(&mut self) {
+        self.record_change();
+        
+        // Snapshotting a large file on every edit would be too slow
+        if self.large_file_mode {
+            return;
+        }
         if self.undo_stack.len() > 100 {
             self.undo_stack.remove(0);
         }
-        self.undo_stack.push(self.content.clone());
+        self.undo_stack.push((self.content.clone(), self.cursor.position()));
         self.redo_stack.clear();
     }
     
+    // Remember the cursor as an edit site. Edits on the same line as the
+    // last one replace it, so typing a word leaves a single entry.
+    fn record_change(&mut self) {
+        let pos = self.cursor.position();
+        if self.change_list.last().is_some_and(|last| last.row == pos.row) {
+            self.change_list.pop();
+        }
+        if self.change_list.len() >= MAX_CHANGES {
+            self.change_list.remove(0);
+        }
+        self.change_list.push(pos);
+        self.change_index = self.change_list.len();
+    }
+    
+    /// Move to an older edit site (`g;`)
+    pub fn goto_prev_change(&mut self) -> Result<()> {
+        if self.change_list.is_empty() {
+            return Err(anyhow!("E664: changelist is empty"));
+        }
+        let mut index = self.change_index.min(self.change_list.len());
+        // Coming from the newest edit, skip the site the cursor is already on
+        if index == self.change_list.len() && self.change_list[index - 1].row == self.cursor.position().row {
+            index -= 1;
+        }
+        if index == 0 {
+            return Err(anyhow!("E662: At start of changelist"));
+        }
+        self.goto_change(index - 1);
+        Ok(())
+    }
+    
+    /// Move to a newer edit site (`g,`)
+    pub fn goto_next_change(&mut self) -> Result<()> {
+        if self.change_list.is_empty() {
+            return Err(anyhow!("E664: changelist is empty"));
+        }
+        if self.change_index + 1 >= self.change_list.len() {
+            return Err(anyhow!("E663: At end of changelist"));
+        }
+        self.goto_change(self.change_index + 1);
+        Ok(())
+    }
+    
+    /// Jump back to the `'` mark (`''` and ``` `` ```), which then marks
+    /// where the cursor was. `linewise` lands on the first non-blank.
+    pub fn goto_jump_mark(&mut self, linewise: bool) -> Result<()> {
+        let mark = self.jump_mark.ok_or_else(|| anyhow!("E20: Mark not set"))?;
+        self.jump_mark = Some(self.cursor.position());
+        let row = mark.row.min(self.content.len().saturating_sub(1));
+        let col = if linewise { first_non_blank(&self.content[row]) } else { mark.col };
+        self.cursor.move_to_position(Position { row, col });
+        self.clamp_cursor();
+        Ok(())
+    }
+    
+    // Lines may have been deleted since the change was recorded
+    fn goto_change(&mut self, index: usize) {
+        self.change_index = index;
+        self.cursor.move_to_position(self.change_list[index]);
+        self.clamp_cursor();
+    }
+    
     pub fn insert_char(&mut self, ch: char) {
         self.push_undo();
         let pos = self.cursor.position();
@@ -97,6 +594,7 @@ impl Buffer {
                 .map(|(i, _)| i)
                 .unwrap_or(line.len());
             line.insert(byte_pos, ch);
+            self.mark_syntax_dirty(pos.row);
             self.cursor.move_right(&self.content);
             self.modified = true;
         }
@@ -115,6 +613,8 @@ impl Buffer {
             let new_line = line[byte_pos..].to_string();
             self.content[pos.row] = line[..byte_pos].to_string();
             self.content.insert(pos.row + 1, new_line);
+            self.mark_syntax_dirty(pos.row);
+            self.mark_syntax_dirty(pos.row + 1);
             
             self.cursor.move_down(&self.content);
             self.cursor.move_to_column(0);
@@ -134,6 +634,7 @@ impl Buffer {
                 let byte_pos = char_indices[pos.col - 1].0;
                 let next_byte_pos = char_indices.get(pos.col).map(|(i, _)| *i).unwrap_or(line.len());
                 line.drain(byte_pos..next_byte_pos);
+                self.mark_syntax_dirty(pos.row);
                 self.cursor.move_left(&self.content);
                 self.modified = true;
             }
@@ -142,6 +643,7 @@ impl Buffer {
             let current_line = self.content.remove(pos.row);
             let prev_line_len = self.content[pos.row - 1].chars().count();
             self.content[pos.row - 1].push_str(&current_line);
+            self.mark_syntax_dirty(pos.row - 1);
             self.cursor.move_up(&self.content);
             self.cursor.move_to_column(prev_line_len);
             self.modified = true;
@@ -161,62 +663,819 @@ impl Buffer {
                 let byte_pos = char_indices[pos.col].0;
                 let next_byte_pos = char_indices.get(pos.col + 1).map(|(i, _)| *i).unwrap_or(line.len());
                 line.drain(byte_pos..next_byte_pos);
+                self.mark_syntax_dirty(pos.row);
                 self.modified = true;
             } else if pos.row + 1 < self.content.len() {
                 // Merge with next line
                 let next_line = self.content.remove(pos.row + 1);
                 self.content[pos.row].push_str(&next_line);
+                self.mark_syntax_dirty(pos.row);
                 self.modified = true;
             }
         }
     }
     
-    pub fn delete_line(&mut self) {
+    /// Delete up to `count` characters from the cursor as one undo step,
+    /// stopping at the end of the line (normal mode `x`). Returns the deleted text.
+    pub fn delete_chars(&mut self, count: usize) -> String {
+        let pos = self.cursor.position();
+        let line_len = self.content.get(pos.row).map_or(0, |line| line.chars().count());
+        let count = count.min(line_len.saturating_sub(pos.col));
+        if count == 0 {
+            return String::new();
+        }
+        
+        let end = Position { row: pos.row, col: pos.col + count };
+        let deleted = self.text_in_range(pos, end);
+        self.replace_range(pos, end, "");
+        self.clamp_cursor_to_line();
+        deleted
+    }
+    
+    /// Delete up to `count` characters before the cursor as one undo step,
+    /// stopping at the start of the line (normal mode `X`). Returns the deleted text.
+    pub fn delete_chars_before(&mut self, count: usize) -> String {
+        let pos = self.cursor.position();
+        let count = count.min(pos.col);
+        if count == 0 {
+            return String::new();
+        }
+        
+        let start = Position { row: pos.row, col: pos.col - count };
+        let deleted = self.text_in_range(start, pos);
+        self.replace_range(start, pos, "");
+        deleted
+    }
+    
+    /// Delete the text between `start` and `end` (inclusive) as one undo
+    /// step, as visual mode `d` does. Returns the deleted text.
+    pub fn delete_selection(&mut self, start: Position, end: Position) -> String {
+        let end = Position { row: end.row, col: end.col + 1 };
+        let deleted = self.text_in_range(start, end);
+        self.replace_range(start, end, "");
+        self.clamp_cursor_to_line();
+        deleted
+    }
+    
+    /// Delete `count` lines from the cursor line down as one undo step
+    /// (`dd`). Returns the deleted lines.
+    pub fn delete_lines(&mut self, count: usize) -> String {
         self.push_undo();
         let pos = self.cursor.position();
+        let end = (pos.row + count.max(1)).min(self.content.len());
+        let deleted: Vec<String> = self.content.drain(pos.row..end).collect();
         
-        if !self.content.is_empty() {
-            if self.content.len() == 1 {
-                self.content[0].clear();
-            } else {
-                self.content.remove(pos.row);
-                if pos.row >= self.content.len() && pos.row > 0 {
-                    self.cursor.move_up(&self.content);
-                }
+        if self.content.is_empty() {
+            self.content.push(String::new());
+        }
+        let row = pos.row.min(self.content.len() - 1);
+        self.mark_syntax_dirty(row);
+        self.cursor.move_to_position(Position { row, col: first_non_blank(&self.content[row]) });
+        self.modified = true;
+        deleted.join("\n")
+    }
+    
+    /// The text from `start` to `end` (exclusive) as a yank copies it. With
+    /// `linewise`, the whole rows from `start.row` to `end.row` instead.
+    pub fn yank_region(&self, start: Position, end: Position, linewise: bool) -> String {
+        if !linewise {
+            return self.text_in_range(start, end);
+        }
+        let end_row = end.row.min(self.content.len().saturating_sub(1));
+        self.content[start.row.min(end_row)..=end_row].join("\n")
+    }
+    
+    /// Put `register` `count` times after the cursor (`p`) or before it
+    /// (`P`) as one undo step. Linewise text goes below/above the current
+    /// line with the cursor on its first non-blank character; charwise text
+    /// goes after/at the cursor with the cursor on its last character.
+    pub fn paste(&mut self, register: &Register, before: bool, count: usize) {
+        let text = vec![register.text.as_str(); count.max(1)];
+        let pos = self.cursor.position();
+        
+        match register.kind {
+            RegisterKind::Linewise => {
+                let row = if before { pos.row } else { (pos.row + 1).min(self.content.len()) };
+                self.push_undo();
+                let lines: Vec<String> = text.join("\n").split('\n').map(|line| line.to_string()).collect();
+                let inserted = lines.len();
+                self.content.splice(row..row, lines);
+                self.mark_syntax_dirty(row);
+                self.mark_syntax_dirty(row + inserted - 1);
+                self.cursor.move_to_position(Position { row, col: first_non_blank(&self.content[row]) });
+                self.modified = true;
             }
-            self.cursor.move_to_column(0);
-            self.modified = true;
+            RegisterKind::Charwise => {
+                let line_len = self.content.get(pos.row).map_or(0, |line| line.chars().count());
+                let col = if before { pos.col } else { (pos.col + 1).min(line_len) };
+                let at = Position { row: pos.row, col };
+                let end = self.replace_range(at, at, &text.concat());
+                self.cursor.move_to_position(Position { row: end.row, col: end.col.saturating_sub(1) });
+            }
+        }
+    }
+    
+    /// Scroll so the cursor is inside a view `rows` high and `cols` wide,
+    /// e.g. after the terminal shrank. Drawing fine-tunes it for wrapped
+    /// lines, folds and the gutter.
+    pub fn fit_view(&self, rows: usize, cols: usize) {
+        let cursor = self.cursor.position();
+        let top = self.top_line.get().min(cursor.row).max((cursor.row + 1).saturating_sub(rows.max(1)));
+        self.top_line.set(top);
+        let line = self.content.get(cursor.row).map_or("", String::as_str);
+        let cells = wrap::cells(line, &[], cursor.col);
+        self.left_col.set(self.left_col.get().min(cells).max((cells + 1).saturating_sub(cols.max(1))));
+    }
+    
+    /// Pull the cursor back inside the buffer, e.g. after lines were removed
+    /// while it was showing another buffer
+    pub fn clamp_cursor(&mut self) {
+        let pos = self.cursor.position();
+        let last_row = self.content.len().saturating_sub(1);
+        if pos.row > last_row {
+            self.cursor.move_to_position(Position { row: last_row, col: pos.col });
         }
+        self.clamp_cursor_to_line();
     }
     
+    // Normal mode keeps the cursor on a character, not past the end of the line
+    fn clamp_cursor_to_line(&mut self) {
+        let pos = self.cursor.position();
+        let line_len = self.content.get(pos.row).map_or(0, |line| line.chars().count());
+        if pos.col >= line_len && line_len > 0 {
+            self.cursor.move_to_column(line_len - 1);
+        }
+    }
+    
+    /// Go back to the content before the last change, with the cursor where
+    /// that change was made
     pub fn undo(&mut self) {
-        if let Some(previous_content) = self.undo_stack.pop() {
-            self.redo_stack.push(self.content.clone());
-            self.content = previous_content;
-            self.modified = true;
+        if let Some((previous_content, pos)) = self.undo_stack.pop() {
+            self.redo_stack.push((std::mem::replace(&mut self.content, previous_content), pos));
+            self.restore_cursor(pos);
         }
     }
     
     pub fn redo(&mut self) {
-        if let Some(next_content) = self.redo_stack.pop() {
-            self.undo_stack.push(self.content.clone());
-            self.content = next_content;
-            self.modified = true;
+        if let Some((next_content, pos)) = self.redo_stack.pop() {
+            self.undo_stack.push((std::mem::replace(&mut self.content, next_content), pos));
+            self.restore_cursor(pos);
         }
     }
     
+    // After swapping in a snapshot
+    fn restore_cursor(&mut self, pos: Position) {
+        self.mark_all_syntax_dirty();
+        self.modified = true;
+        self.cursor.move_to_position(pos);
+        self.clamp_cursor();
+    }
+    
+    /// Replace the text between `start` and `end` (exclusive) with `replacement`
+    /// as a single undo step. Returns the position just after the inserted text.
+    pub fn replace_range(&mut self, start: Position, end: Position, replacement: &str) -> Position {
+        self.push_undo();
+        self.amend_range(start, end, replacement)
+    }
+    
+    /// Like `replace_range`, but as part of the last undo step, e.g. for each
+    /// completion cycled through after the first
+    pub fn amend_range(&mut self, start: Position, end: Position, replacement: &str) -> Position {
+        let (start, end) = if end < start { (end, start) } else { (start, end) };
+        
+        self.delete_range(start, end);
+        let end_pos = self.insert_text_at(start, replacement);
+        
+        self.cursor.move_to_position(end_pos);
+        self.modified = true;
+        end_pos
+    }
+    
+    /// Replace rows `start..=end` with `lines`, or remove them if there are
+    /// none, as a single undo step. The cursor goes to the first new line.
+    pub fn replace_lines(&mut self, start: usize, end: usize, lines: Vec<String>) {
+        let line_start = |row: usize| Position { row, col: 0 };
+        let line_end = |buffer: &Self, row: usize| Position { row, col: buffer.content[row].chars().count() };
+        let text = lines.join("\n");
+        let (from, to, text) = match (lines.is_empty(), end + 1 < self.content.len()) {
+            // Up to the start of the line after, keeping the line break before it
+            (false, true) => (line_start(start), line_start(end + 1), text + "\n"),
+            (false, false) => (line_start(start), line_end(self, end), text),
+            (true, true) => (line_start(start), line_start(end + 1), text),
+            // The last lines go with the line break before them
+            (true, false) if start > 0 => (line_end(self, start - 1), line_end(self, end), text),
+            (true, false) => (line_start(0), line_end(self, end), text),
+        };
+        self.replace_range(from, to, &text);
+        self.cursor.move_to_position(line_start(start));
+        self.clamp_cursor();
+    }
+    
+    /// Replace matches of `regex` in rows `start_row..=end_row` (only the first
+    /// match per line unless `global`) as a single undo step. Returns the number
+    /// of substitutions and of lines changed.
+    pub fn substitute(
+        &mut self,
+        start_row: usize,
+        end_row: usize,
+        regex: &Regex,
+        replacement: &str,
+        global: bool,
+    ) -> (usize, usize) {
+        let end_row = end_row.min(self.content.len().saturating_sub(1));
+        let mut substitutions = 0;
+        let mut changed_lines = 0;
+        let mut last_changed_row = start_row;
+        let mut new_lines = Vec::with_capacity(end_row + 1 - start_row);
+        
+        for row in start_row..=end_row {
+            let line = &self.content[row];
+            let matches = if global { regex.find_iter(line).count() } else { usize::from(regex.is_match(line)) };
+            if matches > 0 {
+                substitutions += matches;
+                changed_lines += 1;
+                last_changed_row = row;
+                let replaced = if global {
+                    regex.replace_all(line, replacement)
+                } else {
+                    regex.replace(line, replacement)
+                };
+                new_lines.push(replaced.into_owned());
+            } else {
+                new_lines.push(line.clone());
+            }
+        }
+        
+        if substitutions > 0 {
+            let end_col = self.content[end_row].chars().count();
+            self.replace_range(
+                Position { row: start_row, col: 0 },
+                Position { row: end_row, col: end_col },
+                &new_lines.join("\n"),
+            );
+            // Like Vim, leave the cursor at the start of the last substituted line
+            let row = last_changed_row.min(self.content.len() - 1);
+            self.cursor.move_to_position(Position { row, col: 0 });
+        }
+        
+        (substitutions, changed_lines)
+    }
+    
+    /// Remove the characters between `start` and `end` (exclusive), joining
+    /// lines when the range spans a line break. Does not record undo.
+    fn delete_range(&mut self, start: Position, end: Position) {
+        if start.row >= self.content.len() {
+            return;
+        }
+        let end = if end.row >= self.content.len() {
+            let last_row = self.content.len() - 1;
+            Position { row: last_row, col: self.content[last_row].chars().count() }
+        } else {
+            end
+        };
+        
+        let start_byte = char_to_byte(&self.content[start.row], start.col);
+        let end_byte = char_to_byte(&self.content[end.row], end.col);
+        
+        if start.row == end.row {
+            if start_byte < end_byte {
+                self.content[start.row].drain(start_byte..end_byte);
+            }
+        } else {
+            let tail = self.content[end.row][end_byte..].to_string();
+            self.content[start.row].truncate(start_byte);
+            self.content[start.row].push_str(&tail);
+            self.content.drain(start.row + 1..=end.row);
+        }
+        self.mark_syntax_dirty(start.row);
+    }
+    
+    /// Insert `text` (which may contain newlines) at `pos`. Does not record
+    /// undo. Returns the position just after the inserted text.
+    fn insert_text_at(&mut self, pos: Position, text: &str) -> Position {
+        if pos.row >= self.content.len() {
+            self.content.resize(pos.row + 1, String::new());
+        }
+        
+        let byte_pos = char_to_byte(&self.content[pos.row], pos.col);
+        let tail = self.content[pos.row].split_off(byte_pos);
+        
+        let mut lines = text.split('\n');
+        let first = lines.next().unwrap_or("");
+        self.content[pos.row].push_str(first);
+        
+        let mut row = pos.row;
+        let mut col = pos.col + first.chars().count();
+        for line in lines {
+            row += 1;
+            self.content.insert(row, line.to_string());
+            col = line.chars().count();
+        }
+        self.content[row].push_str(&tail);
+        self.mark_syntax_dirty(pos.row);
+        self.mark_syntax_dirty(row);
+        
+        Position { row, col }
+    }
+    
+    /// Add `lines` after the last line, as a `:terminal` command prints
+    /// them. The first of them replace the empty line a new buffer starts
+    /// with, and a cursor on the last line keeps following the output. Not
+    /// a change of the user's, so the buffer isn't marked modified.
+    pub fn append_output(&mut self, lines: Vec<String>) {
+        if lines.is_empty() {
+            return;
+        }
+        let last = self.content.len() - 1;
+        let following = self.cursor.position().row == last;
+        let from = if self.changedtick == 0 && self.content == [""] {
+            self.content = lines;
+            0
+        } else {
+            self.content.extend(lines);
+            last + 1
+        };
+        self.mark_syntax_dirty(from);
+        self.mark_syntax_dirty(self.content.len() - 1);
+        if following {
+            self.cursor.move_to_position(Position { row: self.content.len() - 1, col: 0 });
+        }
+    }
+    
+    /// Note that `row` changed and needs to be highlighted again
+    pub fn mark_syntax_dirty(&mut self, row: usize) {
+        // Hints from here on may point at moved text until they are refreshed
+        self.inlay_hints.retain(|&hint_row, _| hint_row < row);
+        self.changedtick += 1;
+        self.folds_dirty_from = Some(self.folds_dirty_from.map_or(row, |from| from.min(row)));
+        self.syntax_dirty_from = Some(self.syntax_dirty_from.map_or(row, |from| from.min(row)));
+        self.syntax_dirty_to = self.syntax_dirty_to.max(row);
+        self.syntax.dirty_from = self.syntax.dirty_from.min(row);
+    }
+    
+    fn mark_all_syntax_dirty(&mut self) {
+        self.mark_syntax_dirty(0);
+        self.mark_syntax_dirty(self.content.len().saturating_sub(1));
+    }
+    
+    /// Bring the syntax cache up to date with the edits since the last
+    /// refresh, as far down as a screen of `height` rows at the cursor can
+    /// show. Rows further down wait until they come into view, so an edit
+    /// in a long file never highlights all the way to its end.
+    pub fn refresh_syntax(&mut self, height: usize) {
+        if let Some(language) = self.language {
+            let limit = (self.cursor.position().row.max(self.top_line.get()) + height + SYNTAX_MARGIN).min(self.content.len());
+            let dirty_from = self.syntax_dirty_from.take();
+            if dirty_from.is_none() && self.syntax.dirty_from >= limit {
+                return;
+            }
+            let from = dirty_from.unwrap_or(self.syntax.dirty_from);
+            let to = std::mem::take(&mut self.syntax_dirty_to);
+            self.syntax.update(&self.content, language, from, to, limit);
+        }
+    }
+    
+    /// Work the folds out again if the text changed since a fold command was
+    /// used, and open the closed folds the cursor has moved inside of
+    pub fn refresh_folds(&mut self, tab_width: usize) {
+        if self.large_file_mode {
+            return;
+        }
+        if self.folds_tick.is_some_and(|tick| tick != self.changedtick) {
+            self.compute_folds(tab_width);
+        }
+        let row = self.cursor.position().row;
+        for fold in &mut self.folds {
+            if fold.closed && fold.contains(row) && fold.start != row {
+                fold.closed = false;
+            }
+        }
+    }
+    
+    // Indent folds that still start on the same row stay closed. Manual
+    // folds after the first changed row move by the lines added or removed,
+    // and ones around it grow or shrink.
+    fn compute_folds(&mut self, tab_width: usize) {
+        // Working out the indent of every line would be too slow
+        if self.large_file_mode {
+            self.folds.clear();
+            return;
+        }
+        let closed: Vec<usize> = self
+            .folds
+            .iter()
+            .filter(|fold| fold.closed && !fold.manual)
+            .map(|fold| fold.start)
+            .collect();
+        let added = self.content.len() as isize - self.folds_len as isize;
+        let changed = self.folds_dirty_from.take();
+        let mut manual: Vec<Fold> = self.folds.iter().filter(|fold| fold.manual).copied().collect();
+        for fold in &mut manual {
+            match changed {
+                Some(row) if fold.start > row => {
+                    fold.start = fold.start.saturating_add_signed(added);
+                    fold.end = fold.end.saturating_add_signed(added);
+                }
+                Some(row) if fold.end >= row => fold.end = fold.end.saturating_add_signed(added),
+                _ => {}
+            }
+        }
+        manual.retain(|fold| fold.start <= fold.end && fold.end < self.content.len());
+        
+        self.folds = fold::indent_folds(&self.content, tab_width);
+        for fold in &mut self.folds {
+            fold.closed = closed.contains(&fold.start);
+        }
+        self.folds.extend(manual);
+        fold::sort(&mut self.folds);
+        self.folds_tick = Some(self.changedtick);
+        self.folds_len = self.content.len();
+    }
+    
+    /// Fold rows `start..=end` by hand, closed, as `zf` does
+    pub fn create_fold(&mut self, start: usize, end: usize, tab_width: usize) {
+        if self.large_file_mode {
+            return;
+        }
+        if self.folds_tick != Some(self.changedtick) {
+            self.compute_folds(tab_width);
+        }
+        self.folds.push(Fold { start, end, closed: true, manual: true });
+        fold::sort(&mut self.folds);
+        let col = self.cursor.position().col;
+        self.cursor.move_to_position(Position { row: start, col });
+        self.clamp_cursor();
+    }
+    
+    /// Take back the manual folds and closed indent folds of a session
+    pub fn restore_folds(&mut self, folds: Vec<Fold>, tab_width: usize) {
+        self.folds = folds;
+        self.folds_len = self.content.len();
+        self.folds_dirty_from = None;
+        self.compute_folds(tab_width);
+    }
+    
+    /// The inlay hints drawn in `row`
+    pub fn shown_hints(&self, row: usize) -> &[InlayHint] {
+        match self.inlay_hints.get(&row) {
+            Some(hints) if self.hints_shown.get() => hints,
+            _ => &[],
+        }
+    }
+    
+    /// The outermost closed fold `row` is in, which is what shows of it
+    pub fn closed_fold_at(&self, row: usize) -> Option<&Fold> {
+        self.folds.iter().find(|fold| fold.closed && fold.contains(row))
+    }
+    
+    /// Open or close folds at the cursor, one level at a time like Vim. A
+    /// cursor inside a fold that closes moves to its first line.
+    pub fn fold(&mut self, command: FoldCommand, tab_width: usize) -> Result<()> {
+        if self.large_file_mode {
+            return Err(anyhow!("folds are off for large files"));
+        }
+        if self.folds_tick != Some(self.changedtick) {
+            self.compute_folds(tab_width);
+        }
+        let row = self.cursor.position().row;
+        let shown = self.folds.iter().position(|fold| fold.closed && fold.contains(row));
+        // The innermost open fold around the one that shows
+        let enclosing = (0..self.folds.len()).rev().find(|&i| {
+            let fold = &self.folds[i];
+            let around = |shown: usize| {
+                i != shown && fold.start <= self.folds[shown].start && fold.end >= self.folds[shown].end
+            };
+            !fold.closed && fold.contains(row) && shown.is_none_or(around)
+        });
+        match (command, shown) {
+            (FoldCommand::OpenAll | FoldCommand::CloseAll, _) => {
+                for fold in &mut self.folds {
+                    fold.closed = command == FoldCommand::CloseAll;
+                }
+            }
+            (FoldCommand::Open | FoldCommand::Toggle, Some(shown)) => self.folds[shown].closed = false,
+            (FoldCommand::Open, None) => {
+                if !self.folds.iter().any(|fold| fold.contains(row)) {
+                    return Err(anyhow!("E490: No fold found"));
+                }
+            }
+            (FoldCommand::Close | FoldCommand::Toggle, _) => match enclosing {
+                Some(i) => self.folds[i].closed = true,
+                None if shown.is_some() => {}
+                None => return Err(anyhow!("E490: No fold found")),
+            },
+        }
+        if let Some(start) = self.closed_fold_at(row).map(|fold| fold.start) {
+            let col = self.cursor.position().col;
+            self.cursor.move_to_position(Position { row: start, col });
+            self.clamp_cursor();
+        }
+        Ok(())
+    }
+    
+    /// Highlights for rows `start..end`, or `None` if the buffer has no language
+    pub fn line_highlights(&self, start: usize, end: usize) -> Option<Vec<Vec<Highlight>>> {
+        self.language
+            .map(|language| self.syntax.highlights(&self.content, language, start, end))
+    }
+    
+    /// The text between `start` and `end` (exclusive), with line breaks as `\n`
+    pub fn text_in_range(&self, start: Position, end: Position) -> String {
+        let (start, end) = if end < start { (end, start) } else { (start, end) };
+        if start.row >= self.content.len() {
+            return String::new();
+        }
+        let end_row = end.row.min(self.content.len() - 1);
+        
+        let mut text = String::new();
+        for row in start.row..=end_row {
+            let line = &self.content[row];
+            let from = if row == start.row { char_to_byte(line, start.col) } else { 0 };
+            let to = if row == end.row { char_to_byte(line, end.col) } else { line.len() };
+            if row > start.row {
+                text.push('\n');
+            }
+            text.push_str(&line[from..to.max(from)]);
+        }
+        text
+    }
+    
+    /// The text selected between `anchor` and `cursor` (both inclusive, in
+    /// either order). Linewise text ends every line with `\n`; blockwise
+    /// text pads lines shorter than the block with spaces.
+    pub fn get_visual_selection_text(&self, anchor: Position, cursor: Position, kind: VisualKind) -> String {
+        let (start, end) = (anchor.min(cursor), anchor.max(cursor));
+        let end_row = end.row.min(self.content.len().saturating_sub(1));
+        match kind {
+            VisualKind::Char => self.text_in_range(start, Position { row: end.row, col: end.col + 1 }),
+            VisualKind::Line => self.content[start.row.min(end_row)..=end_row]
+                .iter()
+                .map(|line| format!("{}\n", line))
+                .collect(),
+            VisualKind::Block => {
+                let (min_col, max_col) = (anchor.col.min(cursor.col), anchor.col.max(cursor.col));
+                let width = max_col - min_col + 1;
+                self.content[start.row.min(end_row)..=end_row]
+                    .iter()
+                    .map(|line| {
+                        let column: String = line.chars().skip(min_col).take(width).collect();
+                        format!("{:<width$}", column, width = width)
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+        }
+    }
+    
+    /// Lines, words, characters and bytes between `start` and `end`
+    /// (exclusive), counting each line break as one character and byte.
+    /// Scripts written without spaces (Chinese, Japanese) count each
+    /// character as a word.
+    pub fn count_stats(&self, start: Position, end: Position) -> TextStats {
+        let text = self.text_in_range(start, end);
+        let mut words = 0;
+        for token in text.split_whitespace() {
+            let mut in_word = false;
+            for grapheme in token.graphemes(true) {
+                if grapheme.chars().next().is_some_and(is_unspaced_script) {
+                    words += 1;
+                    in_word = false;
+                } else if !in_word {
+                    words += 1;
+                    in_word = true;
+                }
+            }
+        }
+        TextStats {
+            lines: start.row.abs_diff(end.row) + 1,
+            words,
+            chars: text.graphemes(true).count(),
+            bytes: text.len(),
+        }
+    }
+    
+    /// Where byte `offset` of the text (from 0, each line break one byte) is,
+    /// the last character if the text is shorter. A line break or a byte
+    /// inside a character is taken as the character before it.
+    pub fn byte_position(&self, offset: usize) -> Position {
+        let mut offset = offset;
+        for (row, line) in self.content.iter().enumerate() {
+            if offset <= line.len() {
+                let byte = (0..=offset).rev().find(|&i| line.is_char_boundary(i)).unwrap_or(0);
+                let col = line[..byte].chars().count();
+                return Position { row, col: col.min(line.chars().count().saturating_sub(1)) };
+            }
+            offset -= line.len() + 1;
+        }
+        let row = self.content.len() - 1;
+        Position { row, col: self.content[row].chars().count().saturating_sub(1) }
+    }
+    
+    /// Every occurrence of `pattern` (a literal string, as `/` searches for),
+    /// as start and exclusive end positions in buffer order. Found again
+    /// only once the text or the pattern changes.
+    pub fn find_all_matches(&self, pattern: &str) -> Matches {
+        if pattern.is_empty() {
+            return Rc::new([]);
+        }
+        if let Some((_, _, matches)) = self.search_matches.borrow().as_ref().filter(|(cached, tick, _)| {
+            cached == pattern && *tick == self.changedtick
+        }) {
+            return Rc::clone(matches);
+        }
+        let len = pattern.chars().count();
+        let matches: Matches = self
+            .content
+            .iter()
+            .enumerate()
+            .flat_map(|(row, line)| {
+                line.match_indices(pattern).map(move |(byte, _)| {
+                    let col = line[..byte].chars().count();
+                    (Position { row, col }, Position { row, col: col + len })
+                })
+            })
+            .collect();
+        *self.search_matches.borrow_mut() = Some((pattern.to_string(), self.changedtick, Rc::clone(&matches)));
+        matches
+    }
+    
+    /// The words of the buffer, split at the same boundaries as the word motions
+    pub fn words(&self) -> impl Iterator<Item = &str> {
+        self.content
+            .iter()
+            .flat_map(|line| line.split(|c: char| !c.is_alphanumeric()))
+            .filter(|word| !word.is_empty())
+    }
+    
+    /// Count how often each word occurs, ignoring case. Keys are lowercase.
+    pub fn compute_word_frequencies(&self) -> HashMap<String, usize> {
+        let mut frequencies = HashMap::new();
+        for word in self.words() {
+            *frequencies.entry(word.to_lowercase()).or_insert(0) += 1;
+        }
+        frequencies
+    }
+    
+    /// The word at `pos` with where it starts and just past where it ends.
+    /// Words are letters, digits and the characters in `iskeyword`.
+    pub fn get_word_at(&self, pos: Position, iskeyword: &[char]) -> Option<(String, Position, Position)> {
+        let chars: Vec<char> = self.content.get(pos.row)?.chars().collect();
+        let is_word = |c: char| c.is_alphanumeric() || iskeyword.contains(&c);
+        if !chars.get(pos.col).is_some_and(|&c| is_word(c)) {
+            return None;
+        }
+        
+        let start = chars[..pos.col].iter().rposition(|&c| !is_word(c)).map_or(0, |i| i + 1);
+        let end = chars[pos.col..].iter().position(|&c| !is_word(c)).map_or(chars.len(), |i| pos.col + i);
+        Some((
+            chars[start..end].iter().collect(),
+            Position { row: pos.row, col: start },
+            Position { row: pos.row, col: end },
+        ))
+    }
+    
+    /// The bracket matching the one at `pos`, looking at most `max_rows`
+    /// rows away. Brackets in strings and comments count like any other.
+    pub fn find_matching_bracket(&self, pos: Position, max_rows: usize) -> Option<Position> {
+        let line: Vec<char> = self.content.get(pos.row)?.chars().collect();
+        let bracket = *line.get(pos.col)?;
+        let (open, close, forward) = match bracket {
+            '(' => ('(', ')', true),
+            '[' => ('[', ']', true),
+            '{' => ('{', '}', true),
+            ')' => ('(', ')', false),
+            ']' => ('[', ']', false),
+            '}' => ('{', '}', false),
+            _ => return None,
+        };
+        
+        let mut depth = 0usize;
+        let rows: Box<dyn Iterator<Item = usize>> = if forward {
+            Box::new(pos.row..self.content.len().min(pos.row + max_rows + 1))
+        } else {
+            Box::new((pos.row.saturating_sub(max_rows)..=pos.row).rev())
+        };
+        for row in rows {
+            let chars: Vec<char> = self.content[row].chars().collect();
+            let cols: Box<dyn Iterator<Item = usize>> = match (forward, row == pos.row) {
+                (true, true) => Box::new(pos.col..chars.len()),
+                (true, false) => Box::new(0..chars.len()),
+                (false, true) => Box::new((0..=pos.col).rev()),
+                (false, false) => Box::new((0..chars.len()).rev()),
+            };
+            for col in cols {
+                let c = chars[col];
+                if c == open || c == close {
+                    // The bracket we started on opens the count
+                    if c == bracket {
+                        depth += 1;
+                    } else {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(Position { row, col });
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+    
+    #[allow(dead_code)]
     pub fn line_count(&self) -> usize {
         self.content.len()
     }
     
+    #[allow(dead_code)]
     pub fn get_line(&self, row: usize) -> Option<&String> {
         self.content.get(row)
     }
 }
 
+/// Column of the first non-whitespace character of `line`
+fn first_non_blank(line: &str) -> usize {
+    line.chars().take_while(|c| c.is_whitespace()).count()
+}
+
+/// Columns `chars` take on screen with tabs `tabstop` wide
+fn display_width(chars: &[char], tabstop: usize) -> usize {
+    chars
+        .iter()
+        .fold(0, |width, &c| if c == '\t' { width + tabstop - width % tabstop } else { width + 1 })
+}
+
+/// `line` with the runs of whitespace holding a tab, and with
+/// `indent.use_tabs` the leading one, made again for the columns they span
+fn retab_line(line: &str, tab_width: usize, indent: DetectedIndent) -> String {
+    let mut retabbed = String::with_capacity(line.len());
+    let mut col = 0;
+    let mut chars = line.chars().peekable();
+    let mut leading = true;
+    while let Some(c) = chars.next() {
+        if c != ' ' && c != '\t' {
+            retabbed.push(c);
+            col += 1;
+            leading = false;
+            continue;
+        }
+        let (from, run_start) = (col, retabbed.len());
+        let mut has_tab = false;
+        let mut next = Some(c);
+        while let Some(c) = next {
+            retabbed.push(c);
+            has_tab |= c == '\t';
+            col += if c == '\t' { tab_width - col % tab_width } else { 1 };
+            next = chars.next_if(|&c| c == ' ' || c == '\t');
+        }
+        if has_tab || (leading && indent.use_tabs) {
+            retabbed.truncate(run_start);
+            let mut at = from;
+            while indent.use_tabs && (at / indent.tab_width + 1) * indent.tab_width <= col {
+                retabbed.push('\t');
+                at = (at / indent.tab_width + 1) * indent.tab_width;
+            }
+            retabbed.push_str(&" ".repeat(col - at));
+        }
+        leading = false;
+    }
+    retabbed
+}
+
+/// Columns the leading whitespace of `line` takes with tabs `tab_width` wide
+pub(super) fn indent_width(line: &str, tab_width: usize) -> usize {
+    let leading: Vec<char> = line.chars().take_while(|c| c.is_whitespace()).collect();
+    display_width(&leading, tab_width)
+}
+
+/// Characters of scripts that don't separate words with spaces
+fn is_unspaced_script(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}'     // Hiragana, Katakana
+        | '\u{3400}'..='\u{4DBF}'   // CJK Extension A
+        | '\u{4E00}'..='\u{9FFF}'   // CJK Unified Ideographs
+        | '\u{F900}'..='\u{FAFF}'   // CJK Compatibility Ideographs
+        | '\u{FF66}'..='\u{FF9F}'   // Halfwidth Katakana
+        | '\u{20000}'..='\u{2FFFF}' // CJK Extensions B and later
+    )
+}
+
+/// Byte offset of character column `col` in `line`, clamped to the line length
+fn char_to_byte(line: &str, col: usize) -> usize {
+    line.char_indices()
+        .nth(col)
+        .map(|(i, _)| i)
+        .unwrap_or(line.len())
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
 pub struct BufferManager {
     buffers: HashMap<usize, Buffer>,
     current_buffer_id: Option<usize>,
+    // The previously current buffer (`#` in the buffer list)
+    alternate_buffer_id: Option<usize>,
     next_id: usize,
 }
 
@@ -225,6 +1484,7 @@ impl BufferManager {
         Self {
             buffers: HashMap::new(),
             current_buffer_id: None,
+            alternate_buffer_id: None,
             next_id: 1,
         }
     }
@@ -239,22 +1499,75 @@ impl BufferManager {
         
         let buffer = Buffer::new(id, name);
         self.buffers.insert(id, buffer);
-        self.current_buffer_id = Some(id);
+        self.set_current(id);
         
         id
     }
     
-    pub fn open_file<P: AsRef<Path>>(&mut self, path: P) -> Result<usize> {
+    /// Open `path` in a new buffer, or switch to the buffer that already has
+    /// it open and return that buffer's id
+    pub fn open_file<P: AsRef<Path>>(&mut self, path: P, config: &Config) -> Result<usize> {
+        if let Some(id) = self.find_buffer_by_path(path.as_ref()) {
+            // The file may have been renamed since it was opened
+            if let Some(buffer) = self.buffers.get_mut(&id) {
+                if let Some(name) = path.as_ref().file_name().and_then(|name| name.to_str()) {
+                    if buffer.name != name {
+                        buffer.name = name.to_string();
+                    }
+                }
+            }
+            self.set_current(id);
+            return Ok(id);
+        }
+        
         let id = self.next_id;
         self.next_id += 1;
         
-        let buffer = Buffer::from_file(id, path)?;
+        let mut buffer = Buffer::from_file(id, path, config)?;
+        if config.editor.detect_indent {
+            buffer.indent = buffer.detect_indent(config.editor.tab_width);
+        }
         self.buffers.insert(id, buffer);
-        self.current_buffer_id = Some(id);
+        self.set_current(id);
         
         Ok(id)
     }
     
+    /// The buffer editing `path`, if any. Paths are compared canonicalized so
+    /// `./foo` and `foo` match; files that don't exist yet are compared as given.
+    pub fn find_buffer_by_path(&self, path: &Path) -> Option<usize> {
+        let canonical = |path: &Path| std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let target = canonical(path);
+        self.list_buffers()
+            .into_iter()
+            .find(|buffer| buffer.path.as_deref().is_some_and(|path| canonical(path) == target))
+            .map(|buffer| buffer.id)
+    }
+    
+    pub fn buffer_mut(&mut self, id: usize) -> Option<&mut Buffer> {
+        self.buffers.get_mut(&id)
+    }
+    
+    // Each buffer keeps its own cursor; switching only makes sure it is
+    // still in bounds
+    fn set_current(&mut self, id: usize) {
+        if self.current_buffer_id != Some(id) {
+            self.alternate_buffer_id = self.current_buffer_id;
+            self.current_buffer_id = Some(id);
+        }
+        if let Some(buffer) = self.buffers.get_mut(&id) {
+            buffer.clamp_cursor();
+        }
+    }
+    
+    pub fn current_buffer_id(&self) -> Option<usize> {
+        self.current_buffer_id
+    }
+    
+    pub fn alternate_buffer_id(&self) -> Option<usize> {
+        self.alternate_buffer_id
+    }
+    
     pub fn current_buffer(&self) -> Option<&Buffer> {
         self.current_buffer_id.and_then(|id| self.buffers.get(&id))
     }
@@ -265,31 +1578,49 @@ impl BufferManager {
     
     pub fn switch_buffer(&mut self, id: usize) -> bool {
         if self.buffers.contains_key(&id) {
-            self.current_buffer_id = Some(id);
+            self.set_current(id);
             true
         } else {
             false
         }
     }
     
-    pub fn close_buffer(&mut self, id: usize) -> Result<()> {
+    /// Close buffer `id` and switch to the alternate buffer. Unsaved changes
+    /// are an error unless `force` is set.
+    pub fn close_buffer(&mut self, id: usize, force: bool) -> Result<()> {
         if let Some(buffer) = self.buffers.get(&id) {
-            if buffer.modified {
-                return Err(anyhow!("Buffer has unsaved changes"));
+            if buffer.modified && !force {
+                return Err(anyhow!("E37: No write since last change (add ! to override)"));
             }
         }
         
         self.buffers.remove(&id);
         
+        if self.alternate_buffer_id == Some(id) {
+            self.alternate_buffer_id = None;
+        }
         if self.current_buffer_id == Some(id) {
-            self.current_buffer_id = self.buffers.keys().next().copied();
+            self.current_buffer_id = None;
+            if let Some(next_id) = self.alternate_buffer_id.take().or_else(|| self.buffers.keys().min().copied()) {
+                self.set_current(next_id);
+            }
         }
         
         Ok(())
     }
     
+    /// Keep every buffer's cursor in view in a window `rows` by `cols`
+    pub fn fit_views(&self, rows: usize, cols: usize) {
+        for buffer in self.buffers.values() {
+            buffer.fit_view(rows, cols);
+        }
+    }
+    
+    /// All buffers, in the order they were opened
     pub fn list_buffers(&self) -> Vec<&Buffer> {
-        self.buffers.values().collect()
+        let mut buffers: Vec<&Buffer> = self.buffers.values().collect();
+        buffers.sort_by_key(|buffer| buffer.id);
+        buffers
     }
     
     pub fn next_buffer(&mut self) {
@@ -297,11 +1628,11 @@ impl BufferManager {
             return;
         }
         
-        let buffer_ids: Vec<usize> = self.buffers.keys().copied().collect();
+        let buffer_ids: Vec<usize> = self.list_buffers().iter().map(|buffer| buffer.id).collect();
         if let Some(current_id) = self.current_buffer_id {
             if let Some(current_pos) = buffer_ids.iter().position(|&id| id == current_id) {
                 let next_pos = (current_pos + 1) % buffer_ids.len();
-                self.current_buffer_id = Some(buffer_ids[next_pos]);
+                self.set_current(buffer_ids[next_pos]);
             }
         }
     }
@@ -311,7 +1642,7 @@ impl BufferManager {
             return;
         }
         
-        let buffer_ids: Vec<usize> = self.buffers.keys().copied().collect();
+        let buffer_ids: Vec<usize> = self.list_buffers().iter().map(|buffer| buffer.id).collect();
         if let Some(current_id) = self.current_buffer_id {
             if let Some(current_pos) = buffer_ids.iter().position(|&id| id == current_id) {
                 let prev_pos = if current_pos == 0 {
@@ -319,7 +1650,7 @@ impl BufferManager {
                 } else {
                     current_pos - 1
                 };
-                self.current_buffer_id = Some(buffer_ids[prev_pos]);
+                self.set_current(buffer_ids[prev_pos]);
             }
         }
     }
@@ -337,15 +1668,26 @@ impl BufferManager {
         }
     }
     
+    /// Up a line, a closed fold counting as one
     pub fn move_cursor_up(&mut self) {
         if let Some(buffer) = self.current_buffer_mut() {
             buffer.cursor.move_up(&buffer.content);
+            while buffer.closed_fold_at(buffer.cursor.position().row).is_some_and(|fold| fold.start < buffer.cursor.position().row) {
+                buffer.cursor.move_up(&buffer.content);
+            }
         }
     }
     
+    /// Down a line, a closed fold counting as one
     pub fn move_cursor_down(&mut self) {
         if let Some(buffer) = self.current_buffer_mut() {
-            buffer.cursor.move_down(&buffer.content);
+            let row = buffer.cursor.position().row;
+            let end = buffer.closed_fold_at(row).map_or(row, |fold| fold.end);
+            if end + 1 < buffer.content.len() {
+                while buffer.cursor.position().row <= end {
+                    buffer.cursor.move_down(&buffer.content);
+                }
+            }
         }
     }
     
@@ -404,9 +1746,32 @@ impl BufferManager {
         }
     }
     
-    pub fn insert_tab(&mut self) {
+    /// Tab in insert mode, in steps of `softtabstop` (or of the indent,
+    /// detected or configured, when that is 0)
+    pub fn insert_tab(&mut self, config: &Config) {
+        if let Some(buffer) = self.current_buffer_mut() {
+            let indent = buffer.indent.unwrap_or_else(|| DetectedIndent::from_config(config));
+            let softtabstop = match config.editor.softtabstop {
+                0 => indent.tab_width,
+                softtabstop => softtabstop,
+            };
+            buffer.insert_tab(softtabstop, config.editor.tab_width, indent.use_tabs);
+        }
+    }
+    
+    /// Shift the cursor line by `levels` indent steps, `<C-T>` and `<C-D>`
+    pub fn indent_current_line(&mut self, levels: isize, config: &Config) {
+        if let Some(row) = self.current_buffer().map(|buffer| buffer.cursor.position().row) {
+            self.indent_rows(row, row, levels, config);
+        }
+    }
+    
+    /// Shift rows `start..=end` by `levels` steps of the buffer's indent, for
+    /// `>`
+    pub fn indent_rows(&mut self, start: usize, end: usize, levels: isize, config: &Config) {
         if let Some(buffer) = self.current_buffer_mut() {
-            buffer.insert_char('\t');
+            let indent = buffer.indent.unwrap_or_else(|| DetectedIndent::from_config(config));
+            buffer.indent_lines(start, end, levels, indent);
         }
     }
     
@@ -422,9 +1787,21 @@ impl BufferManager {
         }
     }
     
-    pub fn delete_line(&mut self) {
+    pub fn delete_chars(&mut self, count: usize) -> Option<String> {
+        self.current_buffer_mut().map(|buffer| buffer.delete_chars(count))
+    }
+    
+    pub fn delete_chars_before(&mut self, count: usize) -> Option<String> {
+        self.current_buffer_mut().map(|buffer| buffer.delete_chars_before(count))
+    }
+    
+    pub fn delete_lines(&mut self, count: usize) -> Option<String> {
+        self.current_buffer_mut().map(|buffer| buffer.delete_lines(count))
+    }
+    
+    pub fn paste(&mut self, register: &Register, before: bool, count: usize) {
         if let Some(buffer) = self.current_buffer_mut() {
-            buffer.delete_line();
+            buffer.paste(register, before, count);
         }
     }
     
@@ -432,6 +1809,7 @@ impl BufferManager {
         if let Some(buffer) = self.current_buffer_mut() {
             let pos = buffer.cursor.position();
             buffer.content.insert(pos.row + 1, String::new());
+            buffer.mark_syntax_dirty(pos.row + 1);
             buffer.cursor.move_down(&buffer.content);
             buffer.cursor.move_to_column(0);
             buffer.modified = true;
@@ -442,6 +1820,7 @@ impl BufferManager {
         if let Some(buffer) = self.current_buffer_mut() {
             let pos = buffer.cursor.position();
             buffer.content.insert(pos.row, String::new());
+            buffer.mark_syntax_dirty(pos.row);
             buffer.cursor.move_to_column(0);
             buffer.modified = true;
         }
@@ -459,9 +1838,39 @@ impl BufferManager {
         }
     }
     
-    pub fn save_current(&mut self) -> Result<()> {
+    pub fn refresh_syntax(&mut self, height: usize) {
         if let Some(buffer) = self.current_buffer_mut() {
-            buffer.save()
+            buffer.refresh_syntax(height);
+        }
+    }
+    
+    pub fn refresh_folds(&mut self, config: &Config) {
+        if let Some(buffer) = self.current_buffer_mut() {
+            let indent = buffer.indent.unwrap_or_else(|| DetectedIndent::from_config(config));
+            buffer.refresh_folds(indent.tab_width);
+        }
+    }
+    
+    pub fn create_fold(&mut self, start: usize, end: usize, config: &Config) {
+        if let Some(buffer) = self.current_buffer_mut() {
+            let indent = buffer.indent.unwrap_or_else(|| DetectedIndent::from_config(config));
+            buffer.create_fold(start, end, indent.tab_width);
+        }
+    }
+    
+    pub fn fold(&mut self, command: FoldCommand, config: &Config) -> Result<()> {
+        match self.current_buffer_mut() {
+            Some(buffer) => {
+                let indent = buffer.indent.unwrap_or_else(|| DetectedIndent::from_config(config));
+                buffer.fold(command, indent.tab_width)
+            }
+            None => Ok(()),
+        }
+    }
+    
+    pub fn save_current(&mut self, config: &Config) -> Result<()> {
+        if let Some(buffer) = self.current_buffer_mut() {
+            buffer.save(config)
         } else {
             Err(anyhow!("No current buffer"))
         }
@@ -471,9 +1880,115 @@ impl BufferManager {
         // TODO: Implement file renaming with UI input
         Ok(())
     }
-    
-    pub fn resume_session(&mut self) -> Result<()> {
-        // TODO: Implement session restoration
-        Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_text(buffer_manager: &mut BufferManager, text: &str) {
+        for ch in text.chars() {
+            buffer_manager.insert_char(ch);
+        }
+    }
+
+    fn cursor(buffer_manager: &BufferManager) -> Position {
+        buffer_manager.current_buffer().unwrap().cursor.position()
+    }
+
+    #[test]
+    fn switching_buffers_keeps_each_cursor_and_content() {
+        let mut buffer_manager = BufferManager::new();
+        let a = buffer_manager.create_buffer("a".to_string());
+        type_text(&mut buffer_manager, "alpha");
+        buffer_manager.insert_newline();
+        type_text(&mut buffer_manager, "beta");
+        let b = buffer_manager.create_buffer("b".to_string());
+        type_text(&mut buffer_manager, "gamma");
+        
+        // Switching lands in normal mode, on the last character at most
+        assert!(buffer_manager.switch_buffer(a));
+        assert_eq!(buffer_manager.current_buffer().unwrap().content, ["alpha", "beta"]);
+        assert_eq!(cursor(&buffer_manager), Position { row: 1, col: 3 });
+        
+        assert!(buffer_manager.switch_buffer(b));
+        assert_eq!(buffer_manager.current_buffer().unwrap().content, ["gamma"]);
+        assert_eq!(cursor(&buffer_manager), Position { row: 0, col: 4 });
+    }
+
+    #[test]
+    fn zero_ctrl_d_clears_the_indent_in_one_undo_step() {
+        let mut buffer_manager = BufferManager::new();
+        buffer_manager.create_buffer("a".to_string());
+        type_text(&mut buffer_manager, "        0");
+        let buffer = buffer_manager.current_buffer_mut().unwrap();
+        buffer.clear_indent_with_marker();
+        assert_eq!(buffer.content, [""]);
+        assert_eq!(buffer.cursor.position(), Position { row: 0, col: 0 });
+        
+        buffer.undo();
+        assert_eq!(buffer.content, ["        0"]);
+    }
+
+    #[test]
+    fn switching_clamps_a_cursor_past_the_end() {
+        let mut buffer_manager = BufferManager::new();
+        let a = buffer_manager.create_buffer("a".to_string());
+        type_text(&mut buffer_manager, "alpha");
+        buffer_manager.insert_newline();
+        type_text(&mut buffer_manager, "beta");
+        buffer_manager.create_buffer("b".to_string());
+        
+        // Lines removed while the buffer was in the background
+        buffer_manager.buffer_mut(a).unwrap().content = vec!["al".to_string()];
+        buffer_manager.switch_buffer(a);
+        assert_eq!(cursor(&buffer_manager), Position { row: 0, col: 1 });
+    }
+
+    fn word_at(line: &str, col: usize) -> Option<(String, usize, usize)> {
+        let mut buffer = Buffer::new(0, "words".to_string());
+        buffer.content = vec![line.to_string()];
+        buffer.get_word_at(Position { row: 0, col }, &['_'])
+            .map(|(word, start, end)| (word, start.col, end.col))
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn word_at_line_edges() {
+        assert_eq!(word_at("foo bar", 0), Some(("foo".to_string(), 0, 3)));
+        assert_eq!(word_at("foo bar", 6), Some(("bar".to_string(), 4, 7)));
+        assert_eq!(word_at("foo bar", 7), None);
+        assert_eq!(word_at("", 0), None);
+    }
+
+    #[test]
+    fn word_at_stops_at_punctuation() {
+        assert_eq!(word_at("a.b(c)", 2), Some(("b".to_string(), 2, 3)));
+        assert_eq!(word_at("a.b(c)", 1), None);
+        assert_eq!(word_at("foo bar", 3), None);
+    }
+
+    #[test]
+    fn word_at_keeps_underscores_and_digits() {
+        assert_eq!(word_at("let snake_case2 = 1", 8), Some(("snake_case2".to_string(), 4, 15)));
+        assert_eq!(word_at("x 42;", 2), Some(("42".to_string(), 2, 4)));
+        assert_eq!(word_at("a-b", 0), Some(("a".to_string(), 0, 1)));
+    }
+
+    #[test]
+    fn word_at_counts_non_ascii_letters_in_chars() {
+        assert_eq!(word_at("çava über", 7), Some(("über".to_string(), 5, 9)));
+        assert_eq!(word_at("变量 x", 1), Some(("变量".to_string(), 0, 2)));
+    }
+
+    #[test]
+    fn large_files_get_no_folds() {
+        let mut buffer = Buffer::new(0, "large".to_string());
+        buffer.content = ["fn main() {", "    body();", "}"].map(String::from).to_vec();
+        buffer.large_file_mode = true;
+        
+        assert!(buffer.fold(FoldCommand::CloseAll, 4).is_err());
+        buffer.create_fold(0, 2, 4);
+        buffer.refresh_folds(4);
+        assert!(buffer.folds.is_empty());
+    }
+}
@@ -0,0 +1,23 @@
+use anyhow::{anyhow, Result};
+use std::process::Command;
+
+// Tools that print the system clipboard, tried in order
+const PASTE_COMMANDS: &[(&str, &[&str])] = &[
+    ("pbpaste", &[]),
+    ("wl-paste", &["--no-newline"]),
+    ("xclip", &["-selection", "clipboard", "-out"]),
+    ("xsel", &["--clipboard", "--output"]),
+    ("powershell.exe", &["-NoProfile", "-Command", "Get-Clipboard -Raw"]),
+];
+
+/// The contents of the system clipboard, the `"+` register in Vim
+pub fn read() -> Result<String> {
+    for (program, args) in PASTE_COMMANDS {
+        if let Ok(output) = Command::new(program).args(*args).output() {
+            if output.status.success() {
+                return Ok(String::from_utf8_lossy(&output.stdout).replace("\r\n", "\n"));
+            }
+        }
+    }
+    Err(anyhow!("No clipboard tool found (pbpaste, wl-paste, xclip or xsel)"))
+}
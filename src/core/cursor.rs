@@ -1,4 +1,4 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Position {
     pub row: usize,
     pub col: usize,
@@ -127,15 +127,9 @@ impl Cursor {
                 return;
             }
             
-            let mut pos = self.position.col;
-            
-            // If we're at the end of a word, move to the end of the next word
-            if pos < chars.len() && chars[pos].is_alphanumeric() {
-                // Skip current word
-                while pos < chars.len() && chars[pos].is_alphanumeric() {
-                    pos += 1;
-                }
-            }
+            // Move at least one character, so at the end of a word we go on
+            // to the end of the next one
+            let mut pos = self.position.col + 1;
             
             // Skip whitespace to get to next word
             while pos < chars.len() && !chars[pos].is_alphanumeric() {
@@ -148,9 +142,7 @@ impl Cursor {
             }
             
             // Back up one to be ON the last character of the word
-            if pos > 0 {
-                pos -= 1;
-            }
+            pos = pos.saturating_sub(1);
             
             // Make sure we don't go past the line
             pos = pos.min(chars.len().saturating_sub(1));
@@ -167,19 +159,19 @@ impl Cursor {
                 let mut pos = self.position.col - 1;
                 
                 // Skip whitespace
-                while pos > 0 && chars.get(pos).map_or(false, |c| c.is_whitespace()) {
+                while pos > 0 && chars.get(pos).is_some_and(|c| c.is_whitespace()) {
                     pos -= 1;
                 }
                 
                 // If we're on alphanumeric, skip current word
-                if chars.get(pos).map_or(false, |c| c.is_alphanumeric()) {
-                    while pos > 0 && chars.get(pos - 1).map_or(false, |c| c.is_alphanumeric()) {
+                if chars.get(pos).is_some_and(|c| c.is_alphanumeric()) {
+                    while pos > 0 && chars.get(pos - 1).is_some_and(|c| c.is_alphanumeric()) {
                         pos -= 1;
                     }
                 }
                 // If we're on non-alphanumeric non-whitespace, skip it
-                else if chars.get(pos).map_or(false, |c| !c.is_alphanumeric() && !c.is_whitespace()) {
-                    while pos > 0 && chars.get(pos - 1).map_or(false, |c| !c.is_alphanumeric() && !c.is_whitespace()) {
+                else if chars.get(pos).is_some_and(|c| !c.is_alphanumeric() && !c.is_whitespace()) {
+                    while pos > 0 && chars.get(pos - 1).is_some_and(|c| !c.is_alphanumeric() && !c.is_whitespace()) {
                         pos -= 1;
                     }
                 }
@@ -252,12 +244,12 @@ impl Cursor {
                 let mut pos = self.position.col - 1;
                 
                 // Skip whitespace
-                while pos > 0 && chars.get(pos).map_or(false, |c| c.is_whitespace()) {
+                while pos > 0 && chars.get(pos).is_some_and(|c| c.is_whitespace()) {
                     pos -= 1;
                 }
                 
                 // Skip current WORD (non-whitespace)
-                while pos > 0 && chars.get(pos - 1).map_or(false, |c| !c.is_whitespace()) {
+                while pos > 0 && chars.get(pos - 1).is_some_and(|c| !c.is_whitespace()) {
                     pos -= 1;
                 }
                 
@@ -273,4 +265,4 @@ impl Cursor {
             }
         }
     }
-} 
\ No newline at end of file
+}
@@ -0,0 +1,26 @@
+use std::path::Path;
+
+/// The filetype of `path` from its extension, named like Vim's `filetype`
+/// (`rust`, `python`, `typescriptreact`, ...)
+pub fn detect(path: &Path) -> Option<&'static str> {
+    Some(match path.extension()?.to_str()? {
+        "rs" => "rust",
+        "py" | "pyi" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "javascriptreact",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "typescriptreact",
+        "go" => "go",
+        "c" | "h" => "c",
+        "cpp" | "hpp" | "cc" | "cxx" => "cpp",
+        "toml" => "toml",
+        "json" => "json",
+        "yaml" | "yml" => "yaml",
+        "md" | "markdown" => "markdown",
+        "sh" | "bash" => "sh",
+        "lua" => "lua",
+        "html" | "htm" => "html",
+        "css" => "css",
+        _ => return None,
+    })
+}
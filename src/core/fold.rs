@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+
+use super::buffer::indent_width;
+
+/// Rows that can be shown as a single line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fold {
+    /// The row the fold is shown as while closed
+    pub start: usize,
+    /// Last row, inclusive
+    pub end: usize,
+    pub closed: bool,
+    /// Made with `zf` rather than found in the indentation
+    #[serde(default)]
+    pub manual: bool,
+}
+
+impl Fold {
+    pub fn contains(&self, row: usize) -> bool {
+        (self.start..=self.end).contains(&row)
+    }
+
+    pub fn lines(&self) -> usize {
+        self.end - self.start + 1
+    }
+}
+
+/// What `za`, `zo`, `zc`, `zR` and `zM` do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FoldCommand {
+    Toggle,
+    Open,
+    Close,
+    OpenAll,
+    CloseAll,
+}
+
+/// Folds from indentation: a line followed by more indented ones folds
+/// together with them, up to the last that isn't blank. All open, in order
+/// of where they start, so enclosing folds come before the ones inside.
+pub fn indent_folds(content: &[String], tab_width: usize) -> Vec<Fold> {
+    let mut folds = Vec::new();
+    // Rows that may still start a fold, with their indent, least indented first
+    let mut open: Vec<(usize, usize)> = Vec::new();
+    let mut last_text = 0;
+    for (row, line) in content.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let indent = indent_width(line, tab_width);
+        while let Some(&(start, _)) = open.last().filter(|&&(_, outer)| outer >= indent) {
+            open.pop();
+            if last_text > start {
+                folds.push(Fold { start, end: last_text, closed: false, manual: false });
+            }
+        }
+        open.push((row, indent));
+        last_text = row;
+    }
+    for (start, _) in open {
+        if last_text > start {
+            folds.push(Fold { start, end: last_text, closed: false, manual: false });
+        }
+    }
+    folds.sort_by_key(|fold| fold.start);
+    folds
+}
+
+/// Sort `folds` by where they start, the longer of two starting together
+/// first so enclosing folds still come before the ones inside
+pub fn sort(folds: &mut [Fold]) {
+    folds.sort_by_key(|fold| (fold.start, std::cmp::Reverse(fold.end)));
+}
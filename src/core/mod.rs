@@ -1,7 +1,16 @@
 pub mod buffer;
+pub mod clipboard;
 pub mod cursor;
+pub mod filetype;
+pub mod fold;
+pub mod outline;
+pub mod project;
+pub mod register;
+#[allow(dead_code)]
 pub mod session;
+pub mod spell;
+pub mod syntax;
+pub mod wrap;
 
-pub use buffer::{Buffer, BufferManager};
-pub use cursor::{Cursor, Position};
-pub use session::SessionManager; 
\ No newline at end of file
+pub use buffer::BufferManager;
+pub use cursor::Position;
@@ -0,0 +1,55 @@
+use regex::Regex;
+use std::path::Path;
+
+/// A definition or heading found by `OutlineParser`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineEntry {
+    /// What was defined: `fn`, `struct`, `class`, `h2`, ...
+    pub kind: String,
+    pub name: String,
+    /// Row in the buffer
+    pub line: usize,
+    /// Nesting depth in columns, e.g. of a method inside an `impl`
+    pub indent: usize,
+}
+
+/// Finds the outline of a file with per-language line patterns, no parser
+pub struct OutlineParser;
+
+impl OutlineParser {
+    /// Outline entries of `content` in line order. Unknown filetypes have none.
+    pub fn parse(content: &[String], filetype: &str) -> Vec<OutlineEntry> {
+        // Each pattern captures the indent, the kind and the name
+        let patterns: &[&str] = match filetype {
+            "rust" => &[
+                r"^(\s*)(?:pub(?:\([^)]*\))? )?(?:const )?(?:async )?(?:unsafe )?(fn) (\w+)",
+                r"^(\s*)(?:pub(?:\([^)]*\))? )?(struct|enum|trait) (\w+)",
+            ],
+            "python" => &[r"^(\s*)(?:async )?(def|class) (\w+)"],
+            "markdown" => &[r"^()(#{1,6}) +(.+?)\s*#*\s*$"],
+            _ => return Vec::new(),
+        };
+        let patterns: Vec<Regex> = patterns.iter().filter_map(|pattern| Regex::new(pattern).ok()).collect();
+
+        content
+            .iter()
+            .enumerate()
+            .filter_map(|(line, text)| {
+                let captures = patterns.iter().find_map(|pattern| pattern.captures(text))?;
+                let kind = &captures[2];
+                let (kind, indent) = if kind.starts_with('#') {
+                    // Headings nest by level
+                    (format!("h{}", kind.len()), (kind.len() - 1) * 2)
+                } else {
+                    (kind.to_string(), captures[1].chars().count())
+                };
+                Some(OutlineEntry { kind, name: captures[3].to_string(), line, indent })
+            })
+            .collect()
+    }
+}
+
+/// The filetype of `path`, if `OutlineParser` knows it
+pub fn filetype(path: &Path) -> Option<&'static str> {
+    super::filetype::detect(path).filter(|filetype| matches!(*filetype, "rust" | "python" | "markdown"))
+}
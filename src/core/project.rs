@@ -0,0 +1,12 @@
+use std::path::{Path, PathBuf};
+
+/// Files and directories that mark the root of a project
+pub const ROOT_MARKERS: &[&str] = &["Cargo.toml", "package.json", "go.mod", "pyproject.toml", "Makefile", ".git"];
+
+/// Walk upward from `start` to the first directory containing one of `markers`
+pub fn find_root(start: &Path, markers: &[&str]) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| markers.iter().any(|marker| dir.join(marker).exists()))
+        .map(|dir| dir.to_path_buf())
+}
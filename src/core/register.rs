@@ -0,0 +1,14 @@
+/// Whether a register holds whole lines or a run of characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegisterKind {
+    Charwise,
+    Linewise,
+}
+
+/// Yanked or deleted text. Lines are separated by `\n`; linewise text has no
+/// trailing newline.
+#[derive(Debug, Clone)]
+pub struct Register {
+    pub text: String,
+    pub kind: RegisterKind,
+}
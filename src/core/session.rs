@@ -2,11 +2,19 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
+use crate::core::fold::Fold;
+
+// How many recently opened files a session remembers
+const MAX_RECENT_FILES: usize = 20;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionData {
     pub buffers: Vec<BufferSession>,
     pub current_buffer_id: Option<usize>,
     pub last_directory: Option<PathBuf>,
+    /// Files opened in this and earlier sessions, most recent first
+    #[serde(default)]
+    pub recent_files: Vec<PathBuf>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,6 +24,9 @@ pub struct BufferSession {
     pub cursor_row: usize,
     pub cursor_col: usize,
     pub modified: bool,
+    /// Manual folds and closed ones
+    #[serde(default)]
+    pub folds: Vec<Fold>,
 }
 
 pub struct SessionManager {
@@ -44,6 +55,30 @@ impl SessionManager {
         Ok(())
     }
     
+    /// Save `session_data` with the files of its buffers put in front of the
+    /// recent files of the previous session
+    pub fn save_with_recent_files(&self, mut session_data: SessionData) -> Result<()> {
+        let previous = self.load().ok().flatten().map(|session| session.recent_files).unwrap_or_default();
+        let mut recent_files: Vec<PathBuf> = Vec::new();
+        let opened = session_data.buffers.iter().filter_map(|buffer| buffer.path.clone());
+        for path in opened.chain(previous) {
+            if !recent_files.contains(&path) {
+                recent_files.push(path);
+            }
+        }
+        recent_files.truncate(MAX_RECENT_FILES);
+        session_data.recent_files = recent_files;
+        self.save(&session_data)
+    }
+    
+    /// Up to `max` recently opened files that still exist
+    pub fn recent_files(&self, max: usize) -> Vec<PathBuf> {
+        let Ok(Some(session)) = self.load() else {
+            return Vec::new();
+        };
+        session.recent_files.into_iter().filter(|path| path.is_file()).take(max).collect()
+    }
+    
     pub fn load(&self) -> Result<Option<SessionData>> {
         if !self.session_file.exists() {
             return Ok(None);
@@ -61,4 +96,4 @@ impl SessionManager {
         }
         Ok(())
     }
-} 
\ No newline at end of file
+}
@@ -0,0 +1,137 @@
+use anyhow::Result;
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::core::Position;
+
+// `z=` lists at most this many words, none more edits away than `MAX_EDITS`
+const MAX_SUGGESTIONS: usize = 10;
+const MAX_EDITS: usize = 2;
+
+/// Checks words against a plain word list, one word per line, like
+/// `/usr/share/dict/words`
+pub struct SpellChecker {
+    words: HashSet<String>,
+}
+
+impl SpellChecker {
+    pub fn load(path: &Path) -> Result<Self> {
+        let words = std::fs::read_to_string(path)?
+            .lines()
+            .map(|word| word.trim().to_lowercase())
+            .filter(|word| !word.is_empty())
+            .collect();
+        Ok(Self { words })
+    }
+
+    /// Byte ranges of the words in `line` that aren't in the list. Anything
+    /// that looks like an identifier (`snake_case`, `camelCase`, digits), an
+    /// acronym or a single letter is left alone.
+    pub fn misspelled(&self, line: &str) -> Vec<(usize, usize)> {
+        let mut ranges = Vec::new();
+        let mut start = None;
+        for (i, c) in line.char_indices().chain(std::iter::once((line.len(), ' '))) {
+            let in_word = c.is_alphanumeric() || c == '_' || c == '\'';
+            match (in_word, start) {
+                (true, None) => start = Some(i),
+                (false, Some(from)) => {
+                    start = None;
+                    let word = line[from..i].trim_matches('\'');
+                    if self.is_misspelled(word) {
+                        let from = from + line[from..i].find(word).unwrap_or(0);
+                        ranges.push((from, from + word.len()));
+                    }
+                }
+                _ => {}
+            }
+        }
+        ranges
+    }
+    
+    /// Whether `word` looks like a word but isn't in the list
+    pub fn is_misspelled(&self, word: &str) -> bool {
+        looks_like_word(word) && !self.words.contains(&word.to_lowercase())
+    }
+    
+    /// Words from the list a couple of edits away from `word`, closest
+    /// first, capitalized like `word`
+    pub fn suggestions(&self, word: &str) -> Vec<String> {
+        let typed: Vec<char> = word.to_lowercase().chars().collect();
+        let mut found: Vec<(usize, &String)> = self
+            .words
+            .iter()
+            .filter(|candidate| candidate.chars().count().abs_diff(typed.len()) <= MAX_EDITS)
+            .filter_map(|candidate| {
+                let edits = edit_distance(&typed, &candidate.chars().collect::<Vec<char>>());
+                (edits > 0 && edits <= MAX_EDITS).then_some((edits, candidate))
+            })
+            .collect();
+        found.sort();
+        found.into_iter().take(MAX_SUGGESTIONS).map(|(_, candidate)| match_case(word, candidate)).collect()
+    }
+    
+    /// Where the next misspelled word after `from` starts, or the one before
+    /// it when `backward`, wrapping around the ends of `content`. Says whether
+    /// it wrapped.
+    pub fn next_misspelled(&self, content: &[String], from: Position, backward: bool) -> Option<(Position, bool)> {
+        let rows = content.len();
+        for step in 0..=rows {
+            let (row, wrapped) = if backward {
+                ((from.row + rows - step % rows) % rows, step > from.row)
+            } else {
+                ((from.row + step) % rows, from.row + step >= rows)
+            };
+            let line = &content[row];
+            let mut cols = self.misspelled(line).into_iter().map(|(start, _)| line[..start].chars().count());
+            // Only the cursor line's first visit is limited to one side of the cursor
+            let found = if backward {
+                cols.rfind(|&col| step > 0 || col < from.col)
+            } else {
+                cols.find(|&col| step > 0 || col > from.col)
+            };
+            if let Some(col) = found {
+                return Some((Position { row, col }, wrapped));
+            }
+        }
+        None
+    }
+}
+
+// Insertions, deletions, substitutions and swaps of neighbours turning `a` into `b`
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut d: Vec<Vec<usize>> = (0..=a.len())
+        .map(|i| (0..=b.len()).map(|j| if i == 0 { j } else if j == 0 { i } else { 0 }).collect())
+        .collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+// `suggestion` in capitals if `word` is, or capitalized if `word` starts with one
+fn match_case(word: &str, suggestion: &str) -> String {
+    let mut chars = word.chars();
+    if chars.next().is_some_and(char::is_uppercase) {
+        if word.chars().count() > 1 && chars.all(|c| !c.is_lowercase()) {
+            return suggestion.to_uppercase();
+        }
+        let mut letters = suggestion.chars();
+        return letters.next().map_or_else(String::new, |first| first.to_uppercase().chain(letters).collect());
+    }
+    suggestion.to_string()
+}
+
+// Letters only, more than one, and capitalized at most at the start
+fn looks_like_word(word: &str) -> bool {
+    let mut chars = word.chars();
+    chars.next().is_some_and(char::is_alphabetic)
+        && word.chars().count() > 1
+        && word.chars().all(|c| c.is_alphabetic() || c == '\'')
+        && chars.all(|c| !c.is_uppercase())
+}
@@ -0,0 +1,297 @@
+use std::path::Path;
+
+/// The kinds of tokens the highlighter picks out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyntaxKind {
+    Keyword,
+    String,
+    Comment,
+    Number,
+}
+
+/// A highlighted byte range `(start, end)` of a line
+pub type Highlight = (usize, usize, SyntaxKind);
+
+/// Highlighter state carried from the end of one line into the next
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineState {
+    #[default]
+    Normal,
+    BlockComment,
+    String(char),
+}
+
+/// Token rules for one language
+#[derive(Debug)]
+pub struct Language {
+    extensions: &'static [&'static str],
+    keywords: &'static [&'static str],
+    line_comment: Option<&'static str>,
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+}
+
+const LANGUAGES: &[Language] = &[
+    Language {
+        extensions: &["rs"],
+        keywords: &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+            "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+            "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super",
+            "trait", "true", "type", "unsafe", "use", "where", "while",
+        ],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"'],
+    },
+    Language {
+        extensions: &["py"],
+        keywords: &[
+            "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+            "elif", "else", "except", "False", "finally", "for", "from", "global", "if", "import",
+            "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return",
+            "True", "try", "while", "with", "yield",
+        ],
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: &['"', '\''],
+    },
+    Language {
+        extensions: &["js", "jsx", "ts", "tsx"],
+        keywords: &[
+            "async", "await", "break", "case", "catch", "class", "const", "continue", "default",
+            "delete", "do", "else", "export", "extends", "false", "finally", "for", "function",
+            "if", "import", "in", "instanceof", "let", "new", "null", "return", "switch", "this",
+            "throw", "true", "try", "typeof", "undefined", "var", "void", "while", "yield",
+        ],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+    },
+    Language {
+        extensions: &["go"],
+        keywords: &[
+            "break", "case", "chan", "const", "continue", "default", "defer", "else", "false",
+            "for", "func", "go", "goto", "if", "import", "interface", "map", "nil", "package",
+            "range", "return", "select", "struct", "switch", "true", "type", "var",
+        ],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '`'],
+    },
+    Language {
+        extensions: &["c", "h", "cpp", "hpp", "cc"],
+        keywords: &[
+            "break", "case", "char", "class", "const", "continue", "default", "do", "double",
+            "else", "enum", "extern", "float", "for", "if", "include", "int", "long", "namespace",
+            "return", "short", "signed", "sizeof", "static", "struct", "switch", "typedef",
+            "union", "unsigned", "void", "while",
+        ],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"'],
+    },
+    Language {
+        extensions: &["toml"],
+        keywords: &["true", "false"],
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: &['"', '\''],
+    },
+];
+
+/// Pick the language for `path` from its extension
+pub fn language_for(path: &Path) -> Option<&'static Language> {
+    let extension = path.extension()?.to_str()?;
+    LANGUAGES.iter().find(|language| language.extensions.contains(&extension))
+}
+
+/// Highlight one line, starting in `state`. Returns the highlighted ranges
+/// and the state at the end of the line.
+pub fn highlight_line(line: &str, state: LineState, language: &Language) -> (Vec<Highlight>, LineState) {
+    let mut highlights = Vec::new();
+    let mut state = state;
+    let mut i = 0;
+
+    while i < line.len() {
+        let rest = &line[i..];
+        match state {
+            LineState::BlockComment => {
+                let end_marker = language.block_comment.map(|(_, end)| end).unwrap_or("");
+                match rest.find(end_marker) {
+                    Some(end) if !end_marker.is_empty() => {
+                        let end = i + end + end_marker.len();
+                        highlights.push((i, end, SyntaxKind::Comment));
+                        state = LineState::Normal;
+                        i = end;
+                    }
+                    _ => {
+                        highlights.push((i, line.len(), SyntaxKind::Comment));
+                        i = line.len();
+                    }
+                }
+            }
+            LineState::String(quote) => {
+                let end = string_end(rest, quote).map(|end| i + end);
+                highlights.push((i, end.unwrap_or(line.len()), SyntaxKind::String));
+                match end {
+                    Some(end) => {
+                        state = LineState::Normal;
+                        i = end;
+                    }
+                    None => i = line.len(),
+                }
+            }
+            LineState::Normal => {
+                if language.line_comment.is_some_and(|marker| rest.starts_with(marker)) {
+                    highlights.push((i, line.len(), SyntaxKind::Comment));
+                    break;
+                }
+                if let Some((start_marker, _)) = language.block_comment.filter(|(start, _)| rest.starts_with(start)) {
+                    // The rest of the comment is handled as BlockComment
+                    highlights.push((i, i + start_marker.len(), SyntaxKind::Comment));
+                    state = LineState::BlockComment;
+                    i += start_marker.len();
+                    continue;
+                }
+
+                let c = rest.chars().next().unwrap_or_default();
+                if language.quotes.contains(&c) {
+                    highlights.push((i, i + 1, SyntaxKind::String));
+                    state = LineState::String(c);
+                    i += 1;
+                } else if c.is_alphanumeric() || c == '_' {
+                    let len = rest
+                        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                        .unwrap_or(rest.len());
+                    let word = &rest[..len];
+                    if c.is_ascii_digit() {
+                        highlights.push((i, i + len, SyntaxKind::Number));
+                    } else if language.keywords.contains(&word) {
+                        highlights.push((i, i + len, SyntaxKind::Keyword));
+                    }
+                    i += len;
+                } else {
+                    i += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    (merge_adjacent(highlights), state)
+}
+
+// Byte offset just past the closing `quote` in `text`, skipping escapes
+fn string_end(text: &str, quote: char) -> Option<usize> {
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if c == quote {
+            return Some(i + c.len_utf8());
+        }
+    }
+    None
+}
+
+// Join touching ranges of the same kind (an opening quote and its string body)
+fn merge_adjacent(highlights: Vec<Highlight>) -> Vec<Highlight> {
+    let mut merged: Vec<Highlight> = Vec::with_capacity(highlights.len());
+    for (start, end, kind) in highlights {
+        match merged.last_mut() {
+            Some(last) if last.1 == start && last.2 == kind => last.1 = end,
+            _ => merged.push((start, end, kind)),
+        }
+    }
+    merged
+}
+
+/// Per-line highlights of a buffer, updated incrementally as lines change
+#[derive(Debug, Clone, Default)]
+pub struct SyntaxCache {
+    pub line_highlights: Vec<Vec<Highlight>>,
+    // State at the end of each line
+    line_states: Vec<LineState>,
+    /// Rows from here on may be stale. Their entries are kept so unchanged
+    /// lines can be reused by the next update.
+    pub dirty_from: usize,
+    // How many lines the content had at the last update, which the cache
+    // stops short of when it was limited
+    content_len: usize,
+}
+
+impl SyntaxCache {
+    /// Re-highlight after rows `from..=to` changed. Rows are in current
+    /// coordinates; lines below `to` are reused (shifted by the change in line
+    /// count) from the first one whose end state matches the cached state.
+    /// Nothing from `limit` on is highlighted: if no line before it matches,
+    /// the rest is dropped, to be highlighted once it's needed.
+    pub fn update(&mut self, content: &[String], language: &Language, from: usize, to: usize, limit: usize) {
+        let old_len = self.line_states.len();
+        let from = from.min(self.dirty_from).min(old_len).min(content.len());
+        // Lines added after `to` was recorded push the edited region down
+        let to = to + content.len().saturating_sub(self.content_len);
+        let delta = content.len() as isize - self.content_len as isize;
+        self.content_len = content.len();
+
+        let mut state = if from == 0 { LineState::Normal } else { self.line_states[from - 1] };
+        let mut highlights = Vec::new();
+        let mut states = Vec::new();
+        let mut old_end = old_len;
+
+        for (row, line) in content.iter().enumerate().skip(from) {
+            if row >= limit {
+                self.line_highlights.truncate(from);
+                self.line_states.truncate(from);
+                self.line_highlights.extend(highlights);
+                self.line_states.extend(states);
+                self.dirty_from = row;
+                return;
+            }
+            let (line_highlights, end_state) = highlight_line(line, state, language);
+            highlights.push(line_highlights);
+            states.push(end_state);
+            state = end_state;
+
+            let old_row = row as isize - delta;
+            if row >= to && old_row >= 0 && (old_row as usize) < old_len && self.line_states[old_row as usize] == end_state {
+                // Everything below is unchanged
+                old_end = old_row as usize + 1;
+                break;
+            }
+        }
+
+        self.line_highlights.splice(from..old_end, highlights);
+        self.line_states.splice(from..old_end, states);
+        self.dirty_from = content.len();
+    }
+
+    /// Highlights for rows `start..end`, from the cache where it is valid and
+    /// computed on the fly (without caching) below `dirty_from`
+    pub fn highlights(&self, content: &[String], language: &Language, start: usize, end: usize) -> Vec<Vec<Highlight>> {
+        let valid = self.dirty_from.min(self.line_highlights.len());
+        let end = end.min(content.len());
+        let mut result: Vec<Vec<Highlight>> = self.line_highlights[start.min(valid)..end.min(valid)].to_vec();
+
+        if end > valid {
+            let mut state = if valid == 0 { LineState::Normal } else { self.line_states[valid - 1] };
+            for (row, line) in content.iter().enumerate().take(end).skip(valid) {
+                let (line_highlights, end_state) = highlight_line(line, state, language);
+                if row >= start {
+                    result.push(line_highlights);
+                }
+                state = end_state;
+            }
+        }
+
+        result
+    }
+
+    /// Forget everything, e.g. when the buffer's language changes
+    pub fn clear(&mut self) {
+        self.line_highlights.clear();
+        self.line_states.clear();
+        self.dirty_from = 0;
+        self.content_len = 0;
+    }
+}
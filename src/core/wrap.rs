@@ -0,0 +1,173 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use super::buffer::InlayHint;
+use crate::config::UIConfig;
+
+/// Cells a grapheme takes on screen. Everything that lays text out goes
+/// through this, so the renderer, the cursor and `gj`/`gk` agree on wide
+/// and combining characters.
+pub fn grapheme_width(grapheme: &str) -> usize {
+    grapheme.width()
+}
+
+/// Where the rows after the first of a wrapped `line` start: with
+/// `break_indent` under its text, `break_indent_shift` further in, but at
+/// most half the `width`
+pub fn break_indent(line: &str, width: usize, config: &UIConfig) -> usize {
+    if !config.break_indent {
+        return 0;
+    }
+    let leading = line.chars().take_while(|c| c.is_whitespace()).count();
+    (leading + config.break_indent_shift).min(width / 2)
+}
+
+/// Places graphemes one after another into rows `width` cells wide whose
+/// continuations start at `indent`. A grapheme never straddles two rows,
+/// and a row always takes at least one, however wide.
+#[derive(Debug, Clone, Copy)]
+pub struct Layout {
+    width: usize,
+    indent: usize,
+    row: usize,
+    used: usize,
+}
+
+impl Layout {
+    pub fn new(width: usize, indent: usize) -> Self {
+        Self { width, indent, row: 0, used: 0 }
+    }
+
+    /// The row and column the next grapheme, `cells` wide, lands on
+    pub fn place(&mut self, cells: usize) -> (usize, usize) {
+        let row_start = if self.row == 0 { 0 } else { self.indent };
+        if self.used + cells > self.width && self.used > row_start {
+            self.row += 1;
+            self.used = self.indent;
+        }
+        let at = (self.row, self.used);
+        self.used += cells;
+        at
+    }
+}
+
+/// The graphemes of `line` as shown, each with the char it starts at and
+/// its width. The `hints` come in as graphemes without a char, before the
+/// text at their column.
+fn graphemes(line: &str, hints: &[InlayHint]) -> Vec<(Option<usize>, usize)> {
+    let mut hints: Vec<&InlayHint> = hints.iter().collect();
+    hints.sort_by_key(|hint| hint.col);
+    let mut hints = hints.into_iter().peekable();
+    let hint_cells = |hint: &InlayHint| hint.text().graphemes(true).map(grapheme_width).collect::<Vec<_>>();
+
+    let mut result = Vec::new();
+    let mut col = 0;
+    for grapheme in line.graphemes(true) {
+        while let Some(hint) = hints.next_if(|hint| hint.col <= col) {
+            result.extend(hint_cells(hint).into_iter().map(|cells| (None, cells)));
+        }
+        result.push((Some(col), grapheme_width(grapheme)));
+        col += grapheme.chars().count();
+    }
+    for hint in hints {
+        result.extend(hint_cells(hint).into_iter().map(|cells| (None, cells)));
+    }
+    result
+}
+
+/// Cells left of char `col` of `line`, the `hints` before it included
+pub fn cells(line: &str, hints: &[InlayHint], col: usize) -> usize {
+    graphemes(line, hints)
+        .into_iter()
+        .take_while(|&(start, _)| start.is_none_or(|start| start < col))
+        .map(|(_, cells)| cells)
+        .sum()
+}
+
+/// The row and column char `col` of `line` lands on once wrapped into rows
+/// `width` cells wide whose continuations start at `indent`. Past the end
+/// is where one more character would go.
+pub fn position(line: &str, hints: &[InlayHint], col: usize, width: usize, indent: usize) -> (usize, usize) {
+    let mut layout = Layout::new(width, indent);
+    for (start, cells) in graphemes(line, hints) {
+        if start.is_some_and(|start| start >= col) {
+            return layout.place(cells);
+        }
+        layout.place(cells);
+    }
+    layout.place(1)
+}
+
+/// Screen rows `line` takes wrapped
+pub fn rows(line: &str, hints: &[InlayHint], width: usize, indent: usize) -> usize {
+    let mut layout = Layout::new(width, indent);
+    graphemes(line, hints)
+        .into_iter()
+        .map(|(_, cells)| layout.place(cells).0)
+        .last()
+        .unwrap_or(0)
+        + 1
+}
+
+/// The char of `line` shown at `row` and column `col` of its wrapped rows,
+/// the nearest one in the row when `col` is in the indent or past the end,
+/// or the char count when the line has no such row. The opposite of
+/// `position`.
+pub fn char_at(line: &str, hints: &[InlayHint], row: usize, col: usize, width: usize, indent: usize) -> usize {
+    let mut layout = Layout::new(width, indent);
+    let mut nearest = None;
+    for (start, cells) in graphemes(line, hints) {
+        let (at_row, at_col) = layout.place(cells);
+        if at_row > row {
+            break;
+        }
+        if let Some(start) = start.filter(|_| at_row == row) {
+            if nearest.is_none() || at_col <= col {
+                nearest = Some(start);
+            }
+        }
+    }
+    nearest.unwrap_or_else(|| line.chars().count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::buffer::InlayKind;
+
+    #[test]
+    fn wide_characters_move_whole_to_the_next_row() {
+        // Four cells fit `ab` and one half of `中`, so it starts the next row
+        let line = "ab中c";
+        assert_eq!(position(line, &[], 2, 3, 0), (1, 0));
+        assert_eq!(position(line, &[], 3, 3, 0), (1, 2));
+        assert_eq!(rows(line, &[], 3, 0), 2);
+        assert_eq!(char_at(line, &[], 1, 1, 3, 0), 2);
+        assert_eq!(char_at(line, &[], 0, 2, 3, 0), 1);
+    }
+
+    #[test]
+    fn combining_marks_take_no_cells() {
+        let line = "e\u{301}tude";
+        assert_eq!(cells(line, &[], 2), 1);
+        assert_eq!(position(line, &[], 2, 3, 0), (0, 1));
+        assert_eq!(position(line, &[], 6, 3, 0), (1, 2));
+    }
+
+    #[test]
+    fn continuation_rows_start_at_the_indent() {
+        assert_eq!(position("abcdef", &[], 4, 4, 1), (1, 1));
+        assert_eq!(char_at("abcdef", &[], 1, 0, 4, 1), 4);
+        assert_eq!(rows("abcdef", &[], 4, 1), 2);
+    }
+
+    #[test]
+    fn hints_push_the_text_after_them() {
+        let hints = [InlayHint { col: 1, label: "n".to_string(), kind: InlayKind::Parameter }];
+        // Shown as `fn: x`
+        assert_eq!(cells("fx", &hints, 1), 4);
+        assert_eq!(position("fx", &hints, 1, 3, 0), (1, 1));
+        assert_eq!(rows("fx", &hints, 3, 0), 2);
+        assert_eq!(char_at("fx", &hints, 1, 0, 3, 0), 1);
+    }
+}
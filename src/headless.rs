@@ -0,0 +1,81 @@
+use anyhow::{anyhow, Result};
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::core::BufferManager;
+use crate::modes::ex::{self, PatternNotFound};
+use crate::modes::ModeManager;
+
+/// Runs ex commands over files without a terminal, for `--eval`
+pub struct HeadlessApp {
+    buffer_manager: BufferManager,
+    mode_manager: ModeManager,
+}
+
+impl HeadlessApp {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            buffer_manager: BufferManager::new(),
+            mode_manager: ModeManager::new(config),
+        }
+    }
+
+    /// Run each of `cmds` once, then open every one of `files`, apply the
+    /// `|`-separated commands in `script` to it and write it if it changed.
+    /// A pattern that matches nothing skips the rest of the file, leaving it
+    /// unwritten; any other error stops the run.
+    pub fn run(&mut self, cmds: &[String], files: &[PathBuf], script: &str) -> Result<()> {
+        for cmd in cmds {
+            self.execute(cmd)?;
+        }
+
+        'files: for file in files {
+            self.mode_manager.edit_file(file, &mut self.buffer_manager)?;
+
+            for command in ex::split_commands(script) {
+                match self.mode_manager.run_command(command, &mut self.buffer_manager) {
+                    Err(e) if e.is::<PatternNotFound>() => continue 'files,
+                    result => result.map_err(|e| anyhow!("{}: {}", command, e))?,
+                }
+            }
+            if self.buffer_manager.current_buffer().is_some_and(|buffer| buffer.modified) {
+                self.execute("w")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, command: &str) -> Result<()> {
+        self.mode_manager
+            .run_command(command, &mut self.buffer_manager)
+            .map_err(|e| anyhow!("{}: {}", command, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn eval_skips_files_without_a_match() {
+        let dir = std::env::temp_dir().join(format!("zen-vim-headless-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (miss, hit) = (dir.join("miss.txt"), dir.join("hit.txt"));
+        fs::write(&miss, "nothing here\n").unwrap();
+        fs::write(&hit, "foo\n").unwrap();
+
+        let result = HeadlessApp::new(&Config::default()).run(&[], &[miss.clone(), hit.clone()], "%s/foo/bar/");
+        let contents = (fs::read_to_string(&miss).unwrap(), fs::read_to_string(&hit).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(result.is_ok(), "{:?}", result);
+        assert_eq!(contents, ("nothing here\n".to_string(), "bar\n".to_string()));
+    }
+
+    #[test]
+    fn eval_stops_at_other_errors() {
+        let result = HeadlessApp::new(&Config::default()).run(&["nosuchcommand".to_string()], &[], "");
+        assert!(result.unwrap_err().to_string().contains("E492"));
+    }
+}
@@ -0,0 +1,561 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::config::{LspConfig, LspServerConfig};
+use crate::core::buffer::{Buffer, Diagnostic, InlayHint, InlayKind, Severity};
+use crate::core::Position;
+
+// The id of the `initialize` request; later requests count up from it
+const INITIALIZE_ID: u64 = 0;
+
+/// What the language servers have to tell the editor
+pub enum LspEvent {
+    /// Everything the server found in `path`, replacing what it sent before
+    Diagnostics { path: PathBuf, diagnostics: Vec<LspDiagnostic> },
+    /// The answer to `request_inlay_hints` for `path`
+    InlayHints { path: PathBuf, hints: Vec<LspInlayHint> },
+    /// A server couldn't be started or stopped unexpectedly
+    Stopped(String),
+}
+
+/// A diagnostic as the server sent it, with columns in UTF-16 code units
+#[derive(Debug, Deserialize)]
+pub struct LspDiagnostic {
+    range: LspRange,
+    severity: Option<u8>,
+    message: String,
+}
+
+/// An inlay hint as the server sent it, with its column in UTF-16 code units
+#[derive(Debug, Deserialize)]
+pub struct LspInlayHint {
+    position: LspPosition,
+    label: LspLabel,
+    kind: Option<u8>,
+}
+
+// A hint's label: text, or parts of text that could each link somewhere
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum LspLabel {
+    Text(String),
+    Parts(Vec<LspLabelPart>),
+}
+
+#[derive(Debug, Deserialize)]
+struct LspLabelPart {
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LspRange {
+    start: LspPosition,
+    end: LspPosition,
+}
+
+#[derive(Debug, Deserialize)]
+struct LspPosition {
+    line: usize,
+    character: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct PublishDiagnosticsParams {
+    uri: String,
+    diagnostics: Vec<LspDiagnostic>,
+}
+
+impl LspDiagnostic {
+    /// Place the diagnostic in `content`, which may have changed since the
+    /// server looked at it
+    pub fn resolve(&self, content: &[String]) -> Diagnostic {
+        let severity = match self.severity {
+            Some(2) => Severity::Warning,
+            Some(3) => Severity::Information,
+            Some(4) => Severity::Hint,
+            // Servers leaving the severity out mostly mean an error
+            _ => Severity::Error,
+        };
+        Diagnostic {
+            start: position_in(content, &self.range.start),
+            end: position_in(content, &self.range.end),
+            severity,
+            message: self.message.clone(),
+        }
+    }
+}
+
+impl LspInlayHint {
+    /// The row of `content` the hint belongs on, and the hint. The `:` the
+    /// server may have put in the label is left to the renderer.
+    pub fn resolve(&self, content: &[String]) -> (usize, InlayHint) {
+        let label = match &self.label {
+            LspLabel::Text(text) => text.clone(),
+            LspLabel::Parts(parts) => parts.iter().map(|part| part.value.as_str()).collect(),
+        };
+        let kind = match self.kind {
+            Some(2) => InlayKind::Parameter,
+            Some(_) => InlayKind::Type,
+            None if label.trim_start().starts_with(':') => InlayKind::Type,
+            None => InlayKind::Parameter,
+        };
+        let position = position_in(content, &self.position);
+        let label = label.trim().trim_start_matches(':').trim_end_matches(':').trim().to_string();
+        (position.row, InlayHint { col: position.col, label, kind })
+    }
+}
+
+// The char position of an LSP line and UTF-16 column, kept inside `content`
+fn position_in(content: &[String], position: &LspPosition) -> Position {
+    let Some(line) = content.get(position.line) else {
+        let row = content.len().saturating_sub(1);
+        return Position { row, col: content.get(row).map_or(0, |line| line.chars().count()) };
+    };
+    let mut units = 0;
+    let col = line
+        .chars()
+        .take_while(|c| {
+            units += c.len_utf16();
+            units <= position.character
+        })
+        .count();
+    Position { row: position.line, col }
+}
+
+// A buffer a server has been told about
+struct Document {
+    path: PathBuf,
+    // The absolute `path` the server knows the document by
+    uri: String,
+    language: String,
+    version: i64,
+    changedtick: u64,
+}
+
+/// Starts a language server for each language as its first file is opened
+/// and keeps it told about the buffers' contents over stdio JSON-RPC
+pub struct LspManager {
+    config: LspConfig,
+    root: PathBuf,
+    // What each started server is sent, by language; `None` for a server
+    // that failed to start, so it isn't tried again on every change
+    servers: HashMap<String, Option<UnboundedSender<Value>>>,
+    // Buffers opened on a server, by buffer id
+    documents: HashMap<usize, Document>,
+    // Buffers no server handles, with the path that was checked, so they
+    // are only looked at again under another name or with another config
+    unserved: HashMap<usize, Option<PathBuf>>,
+    // The id the next request is sent with
+    next_request_id: u64,
+    events_tx: UnboundedSender<LspEvent>,
+    events: UnboundedReceiver<LspEvent>,
+}
+
+impl LspManager {
+    /// Servers are started in `root`, the project they work on
+    pub fn new(config: &LspConfig, root: PathBuf) -> Self {
+        let (events_tx, events) = mpsc::unbounded_channel();
+        Self {
+            config: config.clone(),
+            root,
+            servers: HashMap::new(),
+            documents: HashMap::new(),
+            unserved: HashMap::new(),
+            next_request_id: INITIALIZE_ID + 1,
+            events_tx,
+            events,
+        }
+    }
+
+    /// Use a changed `[lsp]` config. Turning `enabled` off stops the servers.
+    pub fn set_config(&mut self, config: &LspConfig) {
+        if !config.enabled {
+            self.servers.clear();
+            self.documents.clear();
+        }
+        self.unserved.clear();
+        self.config = config.clone();
+    }
+
+    /// Tell the servers about buffers opened, changed or closed since the
+    /// last call. Each change sends the whole text.
+    pub fn sync<'a>(&mut self, buffers: impl IntoIterator<Item = &'a Buffer>) {
+        if !self.config.enabled {
+            return;
+        }
+        let mut open = Vec::new();
+        for buffer in buffers {
+            open.push(buffer.id);
+            if self.unserved.get(&buffer.id) == Some(&buffer.path) {
+                continue;
+            }
+            match self.documents.get_mut(&buffer.id) {
+                Some(document) if buffer.path.as_ref() == Some(&document.path) => {
+                    if document.changedtick != buffer.changedtick {
+                        document.version += 1;
+                        document.changedtick = buffer.changedtick;
+                        let params = json!({
+                            "textDocument": { "uri": document.uri, "version": document.version },
+                            "contentChanges": [{ "text": document_text(buffer) }],
+                        });
+                        send(&self.servers, &document.language, notification("textDocument/didChange", params));
+                    }
+                }
+                _ => {
+                    // New, or saved under another name
+                    self.close(buffer.id);
+                    self.open(buffer);
+                }
+            }
+        }
+
+        let closed: Vec<usize> = self.documents.keys().copied().filter(|id| !open.contains(id)).collect();
+        for id in closed {
+            self.close(id);
+        }
+        self.unserved.retain(|id, _| open.contains(id));
+    }
+
+    /// Ask the server of `buffer` for the inlay hints of its whole text.
+    /// The answer arrives as `LspEvent::InlayHints`. False if no server
+    /// has the buffer open.
+    pub fn request_inlay_hints(&mut self, buffer: &Buffer) -> bool {
+        let Some(document) = self.documents.get(&buffer.id).filter(|_| self.config.enabled) else {
+            return false;
+        };
+        let id = self.next_request_id;
+        self.next_request_id += 1;
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "textDocument/inlayHint",
+            "params": {
+                "textDocument": { "uri": document.uri },
+                "range": {
+                    "start": { "line": 0, "character": 0 },
+                    "end": { "line": buffer.content.len(), "character": 0 },
+                },
+            },
+        });
+        send(&self.servers, &document.language, request);
+        true
+    }
+    
+    /// Diagnostics and errors that arrived since the last call
+    pub fn poll(&mut self) -> Vec<LspEvent> {
+        let mut events = Vec::new();
+        while let Ok(event) = self.events.try_recv() {
+            events.push(event);
+        }
+        events
+    }
+
+    // Send `didOpen` for `buffer` if a server handles its file, starting it if needed
+    fn open(&mut self, buffer: &Buffer) {
+        let extension = buffer.path.as_ref().and_then(|path| path.extension()).and_then(|extension| extension.to_str());
+        let server = extension.and_then(|extension| {
+            self.config.servers.iter().find(|server| server.extensions.iter().any(|e| e == extension))
+        });
+        let (Some(path), Some(server)) = (buffer.path.as_ref(), server) else {
+            self.unserved.insert(buffer.id, buffer.path.clone());
+            return;
+        };
+        self.unserved.remove(&buffer.id);
+        let server = server.clone();
+        if !self.servers.contains_key(&server.language) {
+            let started = self.start(&server);
+            self.servers.insert(server.language.clone(), started);
+        }
+
+        // Servers want absolute paths
+        let uri = path_to_uri(&std::fs::canonicalize(path).unwrap_or_else(|_| self.root.join(path)));
+        let params = json!({
+            "textDocument": {
+                "uri": uri,
+                "languageId": server.language,
+                "version": 0,
+                "text": document_text(buffer),
+            },
+        });
+        send(&self.servers, &server.language, notification("textDocument/didOpen", params));
+        self.documents.insert(
+            buffer.id,
+            Document { path: path.clone(), uri, language: server.language, version: 0, changedtick: buffer.changedtick },
+        );
+    }
+
+    fn close(&mut self, id: usize) {
+        if let Some(document) = self.documents.remove(&id) {
+            let params = json!({ "textDocument": { "uri": document.uri } });
+            send(&self.servers, &document.language, notification("textDocument/didClose", params));
+        }
+    }
+
+    // Spawn the server and the task talking to it. It is killed when its
+    // sender is dropped.
+    fn start(&self, server: &LspServerConfig) -> Option<UnboundedSender<Value>> {
+        let spawned = Command::new(&server.command)
+            .args(&server.args)
+            .current_dir(&self.root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn();
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(e) => {
+                let _ = self.events_tx.send(LspEvent::Stopped(format!("Can't start {}: {}", server.command, e)));
+                return None;
+            }
+        };
+        let (stdin, stdout) = (child.stdin.take()?, child.stdout.take()?);
+
+        let (outgoing_tx, outgoing) = mpsc::unbounded_channel();
+        let events = self.events_tx.clone();
+        let command = server.command.clone();
+        let root = self.root.clone();
+        tokio::spawn(async move {
+            if let Err(e) = run_server(child, stdin, stdout, outgoing, &events, &root).await {
+                let _ = events.send(LspEvent::Stopped(format!("{}: {}", command, e)));
+            }
+        });
+        Some(outgoing_tx)
+    }
+}
+
+// Queue `message` for the server of `language`, if it is running
+fn send(servers: &HashMap<String, Option<UnboundedSender<Value>>>, language: &str, message: Value) {
+    if let Some(Some(server)) = servers.get(language) {
+        let _ = server.send(message);
+    }
+}
+
+fn document_text(buffer: &Buffer) -> String {
+    let mut text = buffer.content.join("\n");
+    if buffer.final_newline {
+        text.push('\n');
+    }
+    text
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+// Initialize the server, then pass messages both ways until the editor
+// drops its sender or the server goes away
+async fn run_server(
+    _child: Child,
+    mut stdin: ChildStdin,
+    stdout: ChildStdout,
+    mut outgoing: UnboundedReceiver<Value>,
+    events: &UnboundedSender<LspEvent>,
+    root: &Path,
+) -> Result<()> {
+    let (incoming_tx, mut incoming) = mpsc::unbounded_channel();
+    tokio::spawn(read_messages(stdout, incoming_tx));
+    // The document each request still waiting for an answer is about
+    let mut requests = HashMap::new();
+
+    let root_uri = path_to_uri(root);
+    let name = root.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let initialize = json!({
+        "jsonrpc": "2.0",
+        "id": INITIALIZE_ID,
+        "method": "initialize",
+        "params": {
+            "processId": std::process::id(),
+            "clientInfo": { "name": "zen-vim" },
+            "rootUri": root_uri,
+            "workspaceFolders": [{ "uri": root_uri, "name": name }],
+            "capabilities": {
+                "textDocument": {
+                    "synchronization": { "dynamicRegistration": false },
+                    "publishDiagnostics": { "relatedInformation": false },
+                    "inlayHint": { "dynamicRegistration": false },
+                },
+            },
+        },
+    });
+    write_message(&mut stdin, &initialize).await?;
+
+    // Nothing else may be sent until the server has answered
+    loop {
+        let message = incoming.recv().await.ok_or_else(|| anyhow!("exited while starting"))?;
+        if message.get("id") == Some(&json!(INITIALIZE_ID)) && message.get("method").is_none() {
+            if let Some(error) = message.get("error") {
+                return Err(anyhow!("initialize failed: {}", error));
+            }
+            break;
+        }
+        handle_message(message, &mut stdin, events, &mut requests).await?;
+    }
+    write_message(&mut stdin, &notification("initialized", json!({}))).await?;
+
+    loop {
+        tokio::select! {
+            message = outgoing.recv() => match message {
+                Some(message) => {
+                    let id = message.get("id").and_then(Value::as_u64);
+                    let uri = message.pointer("/params/textDocument/uri").and_then(Value::as_str);
+                    if let (Some(id), Some(uri)) = (id, uri) {
+                        requests.insert(id, uri.to_string());
+                    }
+                    write_message(&mut stdin, &message).await?;
+                }
+                None => return Ok(()),
+            },
+            message = incoming.recv() => match message {
+                Some(message) => handle_message(message, &mut stdin, events, &mut requests).await?,
+                None => return Err(anyhow!("exited")),
+            },
+        }
+    }
+}
+
+// Act on a notification or request from the server, or on its answer to
+// one of the editor's `requests`
+async fn handle_message(
+    message: Value,
+    stdin: &mut ChildStdin,
+    events: &UnboundedSender<LspEvent>,
+    requests: &mut HashMap<u64, String>,
+) -> Result<()> {
+    let method = message.get("method").and_then(Value::as_str).unwrap_or_default();
+    match (method, message.get("id")) {
+        ("", Some(id)) => {
+            // Inlay hints are all the editor asks for; an error or `null`
+            // means there are none
+            let Some(path) = id.as_u64().and_then(|id| requests.remove(&id)).and_then(|uri| uri_to_path(&uri)) else {
+                return Ok(());
+            };
+            let result = message.get("result").cloned().unwrap_or_default();
+            let hints = serde_json::from_value::<Option<Vec<LspInlayHint>>>(result).ok().flatten().unwrap_or_default();
+            let _ = events.send(LspEvent::InlayHints { path, hints });
+        }
+        ("textDocument/publishDiagnostics", None) => {
+            let Some(params) = message.get("params").cloned() else {
+                return Ok(());
+            };
+            if let Ok(params) = serde_json::from_value::<PublishDiagnosticsParams>(params) {
+                if let Some(path) = uri_to_path(&params.uri) {
+                    let _ = events.send(LspEvent::Diagnostics { path, diagnostics: params.diagnostics });
+                }
+            }
+        }
+        ("", _) | (_, None) => {}
+        (method, Some(id)) => {
+            // Requests get an empty answer, with one entry per item asked
+            // about for `workspace/configuration`
+            let result = match message.pointer("/params/items").and_then(Value::as_array) {
+                Some(items) if method == "workspace/configuration" => json!(vec![Value::Null; items.len()]),
+                _ => Value::Null,
+            };
+            write_message(stdin, &json!({ "jsonrpc": "2.0", "id": id, "result": result })).await?;
+        }
+    }
+    Ok(())
+}
+
+// Read `Content-Length` framed messages until the server closes its output
+async fn read_messages(stdout: ChildStdout, incoming: UnboundedSender<Value>) -> Result<()> {
+    let mut reader = BufReader::new(stdout);
+    loop {
+        let mut length = None;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).await? == 0 {
+                return Ok(());
+            }
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(value) = header.strip_prefix("Content-Length:") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+        let mut body = vec![0; length.ok_or_else(|| anyhow!("message without Content-Length"))?];
+        reader.read_exact(&mut body).await?;
+        if let Ok(message) = serde_json::from_slice(&body) {
+            if incoming.send(message).is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+async fn write_message(stdin: &mut ChildStdin, message: &Value) -> Result<()> {
+    let body = message.to_string();
+    stdin.write_all(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).as_bytes()).await?;
+    stdin.flush().await?;
+    Ok(())
+}
+
+// A `file://` URI, with everything but unreserved characters and `/` escaped
+fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'.' | b'_' | b'~' => uri.push(byte as char),
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let mut rest = uri.strip_prefix("file://")?.as_bytes();
+    let mut bytes = Vec::new();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = tail.get(..2).and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(decoded) if byte == b'%' => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(value: Value) -> (usize, InlayHint) {
+        let content = vec!["let é = f(1);".to_string()];
+        serde_json::from_value::<LspInlayHint>(value).unwrap().resolve(&content)
+    }
+
+    #[test]
+    fn inlay_hint_labels_lose_the_colon_the_renderer_adds() {
+        let (row, hint) = resolve(json!({ "position": { "line": 0, "character": 5 }, "label": ": i32", "kind": 1 }));
+        assert_eq!(row, 0);
+        assert_eq!(hint, InlayHint { col: 5, label: "i32".to_string(), kind: InlayKind::Type });
+
+        let parts = json!([{ "value": "cou" }, { "value": "nt:" }]);
+        let (_, hint) = resolve(json!({ "position": { "line": 0, "character": 10 }, "label": parts, "kind": 2 }));
+        assert_eq!(hint, InlayHint { col: 10, label: "count".to_string(), kind: InlayKind::Parameter });
+    }
+
+    #[test]
+    fn inlay_hint_kind_is_guessed_from_the_label() {
+        let (_, hint) = resolve(json!({ "position": { "line": 0, "character": 5 }, "label": ": u8" }));
+        assert_eq!(hint.kind, InlayKind::Type);
+        let (_, hint) = resolve(json!({ "position": { "line": 0, "character": 10 }, "label": "x:" }));
+        assert_eq!(hint.kind, InlayKind::Parameter);
+    }
+}
@@ -5,11 +5,16 @@ use std::path::PathBuf;
 mod app;
 mod config;
 mod core;
+mod headless;
+mod lsp;
 mod ui;
 mod modes;
 mod picker;
+mod terminal;
 
 use app::App;
+use headless::HeadlessApp;
+use config::Config;
 
 /// Zen-Vim: Minimalist Vim-like editor inspired by Neovim + Snacks
 #[derive(Parser, Debug)]
@@ -24,6 +29,10 @@ struct Args {
     #[arg(short = 'D', long)]
     dashboard: bool,
     
+    /// Don't show the dashboard, even if it is the startup screen
+    #[arg(long, conflicts_with = "dashboard")]
+    no_dashboard: bool,
+    
     /// Config directory
     #[arg(short, long)]
     config: Option<PathBuf>,
@@ -31,11 +40,46 @@ struct Args {
     /// Enable debug logging
     #[arg(short, long)]
     debug: bool,
+    
+    /// Print a commented default config and exit
+    #[arg(long)]
+    generate_config: bool,
+    
+    /// Start with the default config instead of loading config.toml
+    #[arg(long)]
+    norc: bool,
+    
+    /// Don't run the autocommands or make the mappings the config defines
+    #[arg(long)]
+    noplugin: bool,
+    
+    /// Run an ex command before any files are opened (repeatable)
+    #[arg(long = "cmd", value_name = "COMMAND")]
+    cmds: Vec<String>,
+    
+    /// Run an ex command once the opened files are first drawn (repeatable)
+    ///
+    /// `+{command}` among the files does the same: `+42` starts on line 42,
+    /// `+/pattern` on the first match and `+` on the last line.
+    #[arg(long = "startup-cmd", value_name = "COMMAND")]
+    startup_commands: Vec<String>,
+    
+    /// Apply ex commands (separated by |) to each file, write it and exit
+    /// without starting the UI
+    #[arg(long, value_name = "COMMANDS")]
+    eval: Option<String>,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args = Args::parse();
+    let mut args = Args::parse();
+    let mut startup_commands = app::take_plus_commands(&mut args.files);
+    startup_commands.append(&mut args.startup_commands);
+    
+    if args.generate_config {
+        print!("{}", config::generate_default_config_file());
+        return Ok(());
+    }
     
     // Initialize logging
     if args.debug {
@@ -44,14 +88,38 @@ async fn main() -> Result<()> {
             .init();
     }
     
+    // Load configuration
+    let config_dir = args.config.clone().unwrap_or_else(config::config_dir);
+    let config = if args.norc {
+        Config::default()
+    } else {
+        Config::load(args.config)?
+    };
+    let config = if args.noplugin { config.without_plugins() } else { config };
+    
+    if let Some(script) = args.eval {
+        // Batch edit: no terminal, exit 1 on the first error
+        if let Err(e) = HeadlessApp::new(&config).run(&args.cmds, &args.files, &script) {
+            eprintln!("zen-vim: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    
     // Create and run the application
-    let mut app = App::new(args.files, args.config)?;
+    let started_without_files = args.files.is_empty();
+    let mut app = App::new(args.files, config, &args.cmds, startup_commands)?;
+    if !args.norc {
+        app.watch_config(config_dir, args.noplugin);
+    }
     
-    if args.dashboard || app.should_show_dashboard() {
+    if args.dashboard {
         app.show_dashboard().await?;
+    } else if started_without_files {
+        app.show_startup_screen(!args.no_dashboard).await?;
     }
     
     app.run().await?;
     
     Ok(())
-} 
\ No newline at end of file
+}
@@ -0,0 +1,63 @@
+use std::path::Path;
+
+use crate::config::AutocmdConfig;
+
+/// Buffer events that can trigger autocommands. Variants are named after
+/// their Vim counterparts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
+pub enum AutocmdEvent {
+    BufReadPost,
+    BufWritePre,
+    BufWritePost,
+}
+
+impl AutocmdEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AutocmdEvent::BufReadPost => "BufReadPost",
+            AutocmdEvent::BufWritePre => "BufWritePre",
+            AutocmdEvent::BufWritePost => "BufWritePost",
+        }
+    }
+}
+
+/// Whether `autocmd` should run for `event` on the buffer named `name` at `path`.
+/// The pattern is a glob matched against the file name, or the whole path when
+/// it contains a `/`.
+pub fn matches(autocmd: &AutocmdConfig, event: AutocmdEvent, name: &str, path: Option<&Path>) -> bool {
+    if !autocmd.event.eq_ignore_ascii_case(event.name()) {
+        return false;
+    }
+    if autocmd.pattern.contains('/') {
+        path.is_some_and(|path| glob_match(&autocmd.pattern, &path.to_string_lossy()))
+    } else {
+        glob_match(&autocmd.pattern, name)
+    }
+}
+
+/// Minimal glob matching supporting `*` and `?`
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, star_t)) = backtrack {
+            // Let the last `*` swallow one more character
+            p = star + 1;
+            t = star_t + 1;
+            backtrack = Some((star, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
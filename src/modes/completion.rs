@@ -0,0 +1,144 @@
+use std::path::{Path, PathBuf};
+
+use crate::core::buffer::Buffer;
+use crate::core::{BufferManager, Position};
+use crate::picker::PathCompleter;
+
+// Characters file names are completed over, like Vim's `isfname`
+const FILE_NAME_CHARS: &str = "/.-_+,#$%=@";
+
+/// Insert mode completion: keywords for `<C-N>` and `<C-P>`, file names for
+/// `<C-X><C-F>`. Replaces the text before the cursor with one match at a
+/// time.
+#[derive(Debug, Clone)]
+pub struct Completer {
+    /// Where the word being completed starts
+    pub start: Position,
+    /// What was typed before completing
+    pub prefix: String,
+    pub matches: Vec<String>,
+    /// The match in the text; `None` while the prefix is back
+    pub selected: Option<usize>,
+    // Started with `<C-P>`, searching back from the cursor
+    backward: bool,
+}
+
+impl Completer {
+    /// The words of the open buffers starting with the word before the
+    /// cursor, if there are any. Those of the current buffer come first,
+    /// nearest first going forward (or back with `backward`) from the cursor.
+    pub fn keywords(buffer_manager: &BufferManager, iskeyword: &[char], backward: bool) -> Option<Self> {
+        let current = buffer_manager.current_buffer()?;
+        let is_word = |c: char| c.is_alphanumeric() || iskeyword.contains(&c);
+        let (prefix, start) = before_cursor(current, is_word)?;
+
+        // The word being typed doesn't complete itself
+        let mut nearest: Vec<&str> = words_from(current, start, &is_word)
+            .filter(|&(pos, _)| pos != start)
+            .map(|(_, word)| word)
+            .collect();
+        if backward {
+            nearest.reverse();
+        }
+        let others = buffer_manager
+            .list_buffers()
+            .into_iter()
+            .filter(|buffer| buffer.id != current.id)
+            .flat_map(|buffer| words_from(buffer, Position { row: 0, col: 0 }, &is_word).map(|(_, word)| word));
+
+        let mut matches: Vec<String> = Vec::new();
+        for word in nearest.into_iter().chain(others) {
+            if word.len() > prefix.len() && word.starts_with(&prefix) && !matches.iter().any(|seen| seen == word) {
+                matches.push(word.to_string());
+            }
+        }
+        Self::with_matches(start, prefix, matches, backward)
+    }
+
+    /// The entries of the directory the path before the cursor points into
+    /// that start with its last part. Relative paths are taken from the
+    /// directory of the current file.
+    pub fn files(buffer_manager: &BufferManager) -> Option<Self> {
+        let current = buffer_manager.current_buffer()?;
+        let (prefix, start) = before_cursor(current, |c| c.is_alphanumeric() || FILE_NAME_CHARS.contains(c))?;
+        let base = current
+            .path
+            .as_deref()
+            .and_then(Path::parent)
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+        let matches = PathCompleter::new(base)
+            .complete(&prefix)
+            .into_iter()
+            .filter(|path| *path != prefix)
+            .collect();
+        Self::with_matches(start, prefix, matches, false)
+    }
+
+    fn with_matches(start: Position, prefix: String, matches: Vec<String>, backward: bool) -> Option<Self> {
+        if matches.is_empty() {
+            return None;
+        }
+        Some(Self { start, prefix, matches, selected: None, backward })
+    }
+
+    /// Move on to the next match for the key completion started with, back
+    /// for the other one (`backward` for `<C-P>`). Going past either end
+    /// brings the prefix back.
+    pub fn select_next(&mut self, backward: bool) {
+        let last = self.matches.len() - 1;
+        self.selected = match (self.selected, backward != self.backward) {
+            (None, false) => Some(0),
+            (None, true) => Some(last),
+            (Some(i), false) if i < last => Some(i + 1),
+            (Some(i), true) if i > 0 => Some(i - 1),
+            (Some(_), _) => None,
+        };
+    }
+
+    /// What the completed word currently reads
+    pub fn text(&self) -> &str {
+        self.selected.map_or(&self.prefix, |i| &self.matches[i])
+    }
+}
+
+// The run of `is_part` characters just before the cursor, and where it starts
+fn before_cursor(buffer: &Buffer, is_part: impl Fn(char) -> bool) -> Option<(String, Position)> {
+    let cursor = buffer.cursor.position();
+    let before: Vec<char> = buffer.content.get(cursor.row)?.chars().take(cursor.col).collect();
+    let len = before.iter().rev().take_while(|&&c| is_part(c)).count();
+    let text = before[before.len() - len..].iter().collect();
+    Some((text, Position { row: cursor.row, col: cursor.col - len }))
+}
+
+// The words of `buffer` with where they start, from `from` to the end and
+// then from the top back to `from`
+fn words_from<'a>(
+    buffer: &'a Buffer,
+    from: Position,
+    is_word: &'a impl Fn(char) -> bool,
+) -> impl Iterator<Item = (Position, &'a str)> + 'a {
+    let rows = buffer.content.len();
+    (0..=rows).flat_map(move |step| {
+        let row = (from.row + step) % rows;
+        let line = &buffer.content[row];
+        let mut words = Vec::new();
+        let mut start = None;
+        for (col, (i, c)) in line.char_indices().chain(std::iter::once((line.len(), ' '))).enumerate() {
+            match (is_word(c) && i < line.len(), start) {
+                (true, None) => start = Some((col, i)),
+                (false, Some((col_start, byte_start))) => {
+                    start = None;
+                    words.push((Position { row, col: col_start }, &line[byte_start..i]));
+                }
+                _ => {}
+            }
+        }
+        // The cursor line is split between the first step and the last
+        words.into_iter().filter(move |(pos, _)| match step {
+            0 => pos.col >= from.col,
+            step if step == rows => pos.col < from.col,
+            _ => true,
+        })
+    })
+}
@@ -0,0 +1,325 @@
+use anyhow::{anyhow, Result};
+use regex::{Regex, RegexBuilder};
+use std::fmt;
+use std::path::PathBuf;
+
+/// `E486`: a search or `:s` pattern matched nothing. An ex command fails
+/// with it, but `--eval` only skips the file.
+#[derive(Debug)]
+pub struct PatternNotFound(pub String);
+
+impl fmt::Display for PatternNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "E486: Pattern not found: {}", self.0)
+    }
+}
+
+impl std::error::Error for PatternNotFound {}
+
+/// An inclusive range of buffer rows (0-based) given in front of an ex command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Split a leading line range (`%`, `N`, `N,M`, `.`, `$`, `.+N`, ...) off an ex
+/// command. Rows are clamped to the buffer. Returns the range, if one was
+/// given, and the rest of the command.
+pub fn parse_range(command: &str, current_row: usize, line_count: usize) -> (Option<LineRange>, &str) {
+    let last_row = line_count.saturating_sub(1);
+
+    if let Some(rest) = command.strip_prefix('%') {
+        return (Some(LineRange { start: 0, end: last_row }), rest);
+    }
+
+    let (start, rest) = match parse_address(command, current_row, last_row) {
+        Some(parsed) => parsed,
+        None => return (None, command),
+    };
+
+    let (end, rest) = match rest.strip_prefix(',') {
+        Some(after_comma) => parse_address(after_comma, current_row, last_row).unwrap_or((start, after_comma)),
+        None => (start, rest),
+    };
+
+    let (start, end) = if end < start { (end, start) } else { (start, end) };
+    (Some(LineRange { start, end }), rest)
+}
+
+// A single address: `.`, `$` or a 1-based line number, with optional `+N`/`-N` offsets
+fn parse_address(input: &str, current_row: usize, last_row: usize) -> Option<(usize, &str)> {
+    let (mut row, mut rest) = if let Some(rest) = input.strip_prefix('.') {
+        (current_row as i64, rest)
+    } else if let Some(rest) = input.strip_prefix('$') {
+        (last_row as i64, rest)
+    } else {
+        let digits = input.len() - input.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if digits > 0 {
+            let line: i64 = input[..digits].parse().ok()?;
+            (line - 1, &input[digits..])
+        } else if input.starts_with(['+', '-']) {
+            // A bare offset is relative to the current line
+            (current_row as i64, input)
+        } else {
+            return None;
+        }
+    };
+
+    while let Some(sign) = rest.chars().next().filter(|c| *c == '+' || *c == '-') {
+        let after_sign = &rest[1..];
+        let digits = after_sign.len() - after_sign.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let amount: i64 = if digits == 0 { 1 } else { after_sign[..digits].parse().ok()? };
+        row += if sign == '+' { amount } else { -amount };
+        rest = &after_sign[digits..];
+    }
+
+    Some((row.clamp(0, last_row as i64) as usize, rest))
+}
+
+/// A parsed `:s/{pattern}/{replacement}/{flags}` command
+#[derive(Debug, Clone)]
+pub struct Substitute {
+    pub pattern: String,
+    pub replacement: String,
+    pub global: bool,
+    pub ignore_case: bool,
+    /// The `e` flag: a pattern that doesn't match is not an error
+    pub ignore_missing: bool,
+}
+
+impl Substitute {
+    /// Parse a substitute command (range already removed). Returns `None` if
+    /// `command` isn't a substitute command at all.
+    pub fn parse(command: &str) -> Option<Result<Self>> {
+        let rest = command
+            .strip_prefix("substitute")
+            .or_else(|| command.strip_prefix('s'))?;
+        let delimiter = rest.chars().next()?;
+        if delimiter.is_alphanumeric() || delimiter.is_whitespace() || delimiter == '\\' {
+            return None;
+        }
+
+        let mut parts = split_unescaped(&rest[delimiter.len_utf8()..], delimiter).into_iter();
+        let pattern = parts.next().unwrap_or_default();
+        let replacement = parts.next().unwrap_or_default();
+        let flags = parts.next().unwrap_or_default();
+
+        if pattern.is_empty() {
+            return Some(Err(anyhow!("E35: No previous regular expression")));
+        }
+
+        let mut substitute = Self {
+            pattern,
+            replacement,
+            global: false,
+            ignore_case: false,
+            ignore_missing: false,
+        };
+        for flag in flags.trim().chars() {
+            match flag {
+                'g' => substitute.global = true,
+                'i' => substitute.ignore_case = true,
+                'I' => substitute.ignore_case = false,
+                'e' => substitute.ignore_missing = true,
+                other => return Some(Err(anyhow!("E488: Trailing characters: {}", other))),
+            }
+        }
+        Some(Ok(substitute))
+    }
+
+    pub fn regex(&self) -> Result<Regex> {
+        RegexBuilder::new(&vim_to_rust_regex(&self.pattern))
+            .case_insensitive(self.ignore_case)
+            .build()
+            .map_err(|e| anyhow!("E486: Invalid pattern {}: {}", self.pattern, e))
+    }
+
+    /// The replacement in `regex` crate syntax
+    pub fn rust_replacement(&self) -> String {
+        vim_to_rust_replacement(&self.replacement)
+    }
+}
+
+/// The matches a `:s` command still being typed would replace, highlighted
+/// with `ui.inccommand`
+pub struct SubstitutePreview {
+    pub regex: Regex,
+    pub range: LineRange,
+    pub global: bool,
+}
+
+impl SubstitutePreview {
+    /// Byte ranges of the matches the command replaces in `line`, row `row`
+    pub fn matches(&self, row: usize, line: &str) -> Vec<(usize, usize)> {
+        if !(self.range.start..=self.range.end).contains(&row) {
+            return Vec::new();
+        }
+        let matches = self.regex.find_iter(line).take(if self.global { usize::MAX } else { 1 });
+        // Empty matches have nothing to highlight
+        matches.map(|found| (found.start(), found.end())).filter(|(start, end)| end > start).collect()
+    }
+}
+
+/// Split a command line into the commands separated by `|`. A `\|` stays in
+/// its command as is, so patterns can still use it for alternation.
+pub fn split_commands(line: &str) -> Vec<&str> {
+    let mut commands = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        if c == '|' && !escaped {
+            commands.push(line[start..i].trim());
+            start = i + 1;
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    commands.push(line[start..].trim());
+    commands.retain(|command| !command.is_empty());
+    commands
+}
+
+// Split on `delimiter`, treating `\{delimiter}` as a literal delimiter
+fn split_unescaped(input: &str, delimiter: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some(next) if next == delimiter => parts.last_mut().unwrap().push(next),
+                Some(next) => {
+                    let part = parts.last_mut().unwrap();
+                    part.push('\\');
+                    part.push(next);
+                }
+                None => parts.last_mut().unwrap().push('\\'),
+            }
+        } else if c == delimiter {
+            parts.push(String::new());
+        } else {
+            parts.last_mut().unwrap().push(c);
+        }
+    }
+    parts
+}
+
+/// Translate a Vim "magic" pattern (`\+`, `\(\)`, `\<`, literal `+`/`(`) to `regex` syntax
+pub fn vim_to_rust_regex(pattern: &str) -> String {
+    let mut out = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(c @ ('+' | '?' | '|' | '(' | ')' | '{' | '}')) => out.push(c),
+                Some('=') => out.push('?'),
+                Some('<' | '>') => out.push_str("\\b"),
+                Some('t') => out.push_str("\\t"),
+                Some('n') => out.push_str("\\n"),
+                Some(c @ ('s' | 'S' | 'd' | 'D' | 'w' | 'W' | 'b' | 'B')) => {
+                    out.push('\\');
+                    out.push(c);
+                }
+                Some(c) => out.push_str(&regex::escape(&c.to_string())),
+                None => out.push_str("\\\\"),
+            },
+            '+' | '?' | '|' | '(' | ')' | '{' | '}' => {
+                out.push('\\');
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Translate a Vim replacement string (`&`, `\1`, `\r`) to `regex` syntax
+pub fn vim_to_rust_replacement(replacement: &str) -> String {
+    let mut out = String::with_capacity(replacement.len());
+    let mut chars = replacement.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => out.push_str("${0}"),
+            '$' => out.push_str("$$"),
+            '\\' => match chars.next() {
+                Some(d @ '0'..='9') => {
+                    out.push_str("${");
+                    out.push(d);
+                    out.push('}');
+                }
+                Some('r' | 'n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('$') => out.push_str("$$"),
+                Some(c) => out.push(c),
+                None => out.push('\\'),
+            },
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The file of a `:w >> {file}` command (range already removed), empty if
+/// none was given. `None` if `command` doesn't append.
+pub fn parse_append(command: &str) -> Option<&str> {
+    let rest = command.strip_prefix("write").or_else(|| command.strip_prefix('w'))?;
+    Some(rest.trim_start().strip_prefix(">>")?.trim())
+}
+
+/// The shell command of a `:ter[minal] [cmd]` command, empty if none was
+/// given. `None` if `command` isn't one.
+pub fn parse_terminal(command: &str) -> Option<&str> {
+    let name_len = command.len() - command.trim_start_matches(|c: char| c.is_ascii_alphabetic()).len();
+    if name_len < 3 || !"terminal".starts_with(&command[..name_len]) {
+        return None;
+    }
+    let rest = &command[name_len..];
+    (rest.is_empty() || rest.starts_with(' ')).then(|| rest.trim())
+}
+
+/// Whether a `:retab[!] [N]` command (range already removed) has the `!`,
+/// and its new tab width. `None` if `command` isn't one.
+pub fn parse_retab(command: &str) -> Option<Result<(bool, Option<usize>)>> {
+    let name_len = command.len() - command.trim_start_matches(|c: char| c.is_ascii_alphabetic()).len();
+    if name_len < 3 || !"retab".starts_with(&command[..name_len]) {
+        return None;
+    }
+    let rest = &command[name_len..];
+    let (bang, width) = match rest.strip_prefix('!') {
+        Some(width) => (true, width.trim()),
+        None => (false, rest.trim()),
+    };
+    if width.is_empty() {
+        return Some(Ok((bang, None)));
+    }
+    Some(match width.parse() {
+        Ok(width) if width > 0 => Ok((bang, Some(width))),
+        _ => Err(anyhow!("E475: Invalid argument: {}", width)),
+    })
+}
+
+/// The byte a `:go[to] [N]` command goes to, counting from 1 at the start
+/// of the buffer. `None` if `command` isn't one.
+pub fn parse_goto(command: &str) -> Option<Result<usize>> {
+    let name_len = command.len() - command.trim_start_matches(|c: char| c.is_ascii_alphabetic()).len();
+    if name_len < 2 || !"goto".starts_with(&command[..name_len]) {
+        return None;
+    }
+    let byte = command[name_len..].trim();
+    if byte.is_empty() {
+        return Some(Ok(1));
+    }
+    Some(byte.parse().map_err(|_| anyhow!("E475: Invalid argument: {}", byte)))
+}
+
+/// Expand a leading `~` to the home directory. An empty path means home too,
+/// as with a bare `:cd`.
+pub fn expand_home(path: &str) -> PathBuf {
+    let home = || PathBuf::from(std::env::var("HOME").unwrap_or_else(|_| ".".to_string()));
+    match path {
+        "" | "~" => home(),
+        _ => match path.strip_prefix("~/") {
+            Some(rest) => home().join(rest),
+            None => PathBuf::from(path),
+        },
+    }
+}
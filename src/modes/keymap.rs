@@ -0,0 +1,113 @@
+// Static reference tables for the implemented keybindings and ex commands.
+// These back the `:help` screen, so keep them in sync with the handlers in
+// `modes/mod.rs` and `App::handle_leader_key`. A test runs every entry of
+// `EX_COMMANDS` to check it is a command `execute_command` knows.
+
+pub const NORMAL_KEYS: &[(&str, &str)] = &[
+    ("h j k l", "Move cursor left/down/up/right"),
+    ("w / b", "Next / previous word"),
+    ("W / B", "Next / previous WORD (whitespace separated)"),
+    ("e / E", "End of word"),
+    ("0 / $", "Line start / end"),
+    ("gg / G", "First / last line"),
+    ("[N]gj / gk", "Down / up a screen row, within wrapped lines"),
+    ("g; / g,", "Older / newer edit site"),
+    ("'' / ``", "Back to the line / position before the last jump (G, gg, searches, :N, :goto)"),
+    ("i / a", "Insert before / after cursor"),
+    ("I / A", "Insert at line start / end"),
+    ("o / O", "Open line below / above"),
+    ("v", "Visual mode (motions extend the selection)"),
+    ("Ctrl-g", "Show the file name, whether it's modified, its lines and how far down the cursor is"),
+    ("g Ctrl-g", "Line, word, character and byte counts (of the selection in visual mode)"),
+    (":", "Command line"),
+    ("/", "Search forward"),
+    ("n / N", "Next / previous search match"),
+    ("* / #", "Search word under cursor forward / backward"),
+    ("~", "Toggle case of character"),
+    ("]s / [s", "Next / previous misspelled word (with spell on)"),
+    ("z=", "Suggest corrections for the word under the cursor"),
+    ("za / zo / zc", "Toggle / open / close the indent fold at the cursor"),
+    ("zR / zM", "Open / close every fold"),
+    ("zf{motion}", "Fold the lines the motion moves over, e.g. zfj or zfG"),
+    ("ga", "Show the character under the cursor as decimal, hex, octal and U+ code point"),
+    ("[N]x / [N]X", "Delete N characters under / before cursor"),
+    ("[N]dd", "Delete N lines"),
+    ("[N]yy", "Yank N lines"),
+    ("[N]d / y / c{motion}", "Delete / yank / change over a motion (h j k l w W b B e 0 $ gg G)"),
+    ("!{motion} / [N]!!", "Filter the motion's lines / N lines through a shell command"),
+    ("={motion} / [N]==", "Format the motion's lines / N lines with the filetype's formatter"),
+    ("Y", "Yank to end of line"),
+    ("\"{a-z}", "Use register a-z for the next yank, delete or put"),
+    ("[N]p / [N]P", "Put after / before cursor (lines below / above)"),
+    ("y / d / c (visual)", "Yank / delete / change the selection"),
+    ("gn", "Select the next search match"),
+    ("gi", "Insert where insert mode was last left"),
+    ("cgn", "Change the next search match (. repeats)"),
+    ("u / Ctrl-r", "Undo / redo"),
+    ("Ctrl-n / Ctrl-p", "Next / previous buffer"),
+    ("Ctrl-w o", "Close the other windows (the file explorer)"),
+    ("Ctrl-w c / q", "Close the current window / quit from the last one"),
+    ("Ctrl-w h / l", "Go to the file explorer / the editor"),
+    ("Ctrl-w w", "Go to the other window"),
+    ("Ctrl-w > / <", "Narrow / widen the file explorer by count columns"),
+    ("Ctrl-w =", "Give the file explorer its usual width back"),
+    ("ZZ / ZQ", "Write and close / close without writing the buffer"),
+    ("? / g?", "Show this help"),
+    ("q", "Quit"),
+];
+
+pub const LEADER_KEYS: &[(&str, &str)] = &[
+    ("<space>f", "Find files"),
+    ("<space>/", "Grep text"),
+    ("<space>b", "Buffer list (Ctrl-d closes a buffer)"),
+    ("<space>s", "Resume session"),
+    ("<space>r", "Rename file"),
+    ("<space>d", "Show dashboard"),
+    ("<space>e", "File explorer (again to hide; Esc back to the editor)"),
+    ("<space>l", "Lines of the current buffer"),
+    ("<space>o", "Outline: functions, types and headings"),
+    ("<space>p / <space>P", "Put the system clipboard after / before the cursor"),
+    ("<space>W", "Word frequencies (Enter: first use, *: search)"),
+    ("<space>z", "Toggle zen mode (Esc leaves it)"),
+    ("<space>q", "Close buffer"),
+    ("<space>Q", "Quit"),
+];
+
+pub const INSERT_KEYS: &[(&str, &str)] = &[
+    ("Esc", "Back to normal mode"),
+    ("Ctrl-v {code}", "Insert by code: 065, u00e9, U0001f600, x41, o101"),
+    ("Ctrl-k {a}{b}", "Insert a digraph, e.g. -> for an arrow"),
+    ("Ctrl-t / Ctrl-d", "Indent / dedent the line by one step"),
+    ("0 Ctrl-d / ^ Ctrl-d", "Remove all indent from the line"),
+    ("Ctrl-n / Ctrl-p", "Complete the word before the cursor from the open buffers"),
+    ("Ctrl-x Ctrl-f", "Complete the file name before the cursor, from the file's directory"),
+    ("Ctrl-y / Ctrl-e", "Keep the completion / go back to what was typed"),
+];
+
+pub const EX_COMMANDS: &[(&str, &str)] = &[
+    (":w", "Write the current buffer"),
+    (":w {file}", "Write the current buffer to {file}"),
+    (":[range]w >> {file}", "Append the range (all lines by default) to {file}"),
+    (":wq / :x", "Write the current buffer and quit"),
+    (":e {file}", "Edit {file}"),
+    (":cd {dir}", "Change the working directory (~ for home)"),
+    (":pwd", "Show the working directory"),
+    (":se[t] [no]{option}", "Set number, wrap, list, spell, expandtab, breakindent or paste ({option}! toggles, {option}? shows)"),
+    (":[range]ret[ab][!] [N]", "Tabs to spaces (! indentation to tabs), all lines by default; N sets the tab width"),
+    (":[range]uni[q]", "Remove repeated lines next to each other (all lines by default)"),
+    (":[range]rev[erse]", "Reverse the order of the lines (all lines by default)"),
+    (":[range]s/pat/rep/[g]", "Substitute pat with rep (% for all lines)"),
+    (":{N}", "Go to line {N}"),
+    (":go[to] [N]", "Go to byte {N} of the buffer, counting from 1"),
+    (":[n|i]map {lhs} {rhs}", "Map keys; {rhs} is expanded through other maps"),
+    (":[n|i]noremap {lhs} {rhs}", "Map keys without expanding {rhs}"),
+    (":h[elp]", "Show this help"),
+    (":mes[sages]", "Show recent messages"),
+    (":on[ly]", "Close the other windows (the file explorer)"),
+    (":{range}!{cmd}", "Filter the lines through {cmd}; without a range, like :terminal"),
+    (":ter[minal] {cmd}", "Run {cmd} in the shell, its output in a new buffer"),
+    (":mks[ession]", "Save the session now (see [session] in the config)"),
+    (":ZenMode", "Toggle zen mode"),
+    (":LspInlayHints", "Refresh inlay hints from the language server"),
+    ("/{pattern}", "Search forward for {pattern}"),
+];
@@ -0,0 +1,227 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Keys collected after `<C-V>` or `<C-K>` in insert mode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LiteralInsert {
+    /// `<C-V>` was typed; the next key picks how the code is written, or is
+    /// inserted as is
+    Start,
+    /// Digits of a code point, ended by `max_digits` digits or `Enter`
+    Code {
+        radix: u32,
+        max_digits: usize,
+        // The key that picked the radix, inserted if no digits follow
+        prefix: Option<char>,
+        digits: String,
+    },
+    /// `<C-K>`, with the first digraph character once typed
+    Digraph(Option<char>),
+}
+
+/// What `LiteralInsert::feed` made of a key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LiteralFeed {
+    /// The sequence needs more keys
+    Pending(LiteralInsert),
+    /// The sequence is over. `consumed` is false when the key wasn't part of
+    /// it and should be handled as usual.
+    Done { ch: Option<char>, consumed: bool },
+}
+
+impl LiteralInsert {
+    pub fn feed(self, key: KeyEvent) -> LiteralFeed {
+        let typed = match key.code {
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => Some(c),
+            _ => None,
+        };
+
+        match self {
+            LiteralInsert::Start => {
+                let (radix, max_digits, prefix) = match typed {
+                    Some(c @ '0'..='9') => {
+                        return LiteralFeed::Pending(LiteralInsert::Code {
+                            radix: 10,
+                            max_digits: 3,
+                            prefix: None,
+                            digits: c.to_string(),
+                        });
+                    }
+                    Some(c @ ('x' | 'X')) => (16, 2, c),
+                    Some(c @ 'u') => (16, 4, c),
+                    Some(c @ 'U') => (16, 8, c),
+                    Some(c @ ('o' | 'O')) => (8, 3, c),
+                    Some(c) => return LiteralFeed::Done { ch: Some(c), consumed: true },
+                    None if key.code == KeyCode::Tab => {
+                        return LiteralFeed::Done { ch: Some('\t'), consumed: true };
+                    }
+                    None => return LiteralFeed::Done { ch: None, consumed: false },
+                };
+                LiteralFeed::Pending(LiteralInsert::Code {
+                    radix,
+                    max_digits,
+                    prefix: Some(prefix),
+                    digits: String::new(),
+                })
+            }
+            LiteralInsert::Code { radix, max_digits, prefix, mut digits } => {
+                match typed {
+                    Some(c) if c.is_digit(radix) => {
+                        digits.push(c);
+                        if digits.len() < max_digits {
+                            return LiteralFeed::Pending(LiteralInsert::Code { radix, max_digits, prefix, digits });
+                        }
+                        LiteralFeed::Done { ch: code_point(&digits, radix), consumed: true }
+                    }
+                    _ => {
+                        // Like `<C-V>u` followed by a non-digit: insert the `u`
+                        let ch = if digits.is_empty() { prefix } else { code_point(&digits, radix) };
+                        LiteralFeed::Done { ch, consumed: key.code == KeyCode::Enter }
+                    }
+                }
+            }
+            LiteralInsert::Digraph(first) => match (first, typed) {
+                (None, Some(c)) => LiteralFeed::Pending(LiteralInsert::Digraph(Some(c))),
+                // An unknown digraph inserts its second character
+                (Some(first), Some(second)) => LiteralFeed::Done {
+                    ch: Some(digraph(first, second).unwrap_or(second)),
+                    consumed: true,
+                },
+                (_, None) => LiteralFeed::Done { ch: None, consumed: false },
+            },
+        }
+    }
+}
+
+fn code_point(digits: &str, radix: u32) -> Option<char> {
+    u32::from_str_radix(digits, radix).ok().and_then(char::from_u32)
+}
+
+/// The character for the digraph `first second`, tried in either order
+pub fn digraph(first: char, second: char) -> Option<char> {
+    let lookup = |a: char, b: char| {
+        DIGRAPHS
+            .iter()
+            .find(|&&(keys, _)| keys.chars().eq([a, b]))
+            .map(|&(_, ch)| ch)
+    };
+    lookup(first, second).or_else(|| lookup(second, first))
+}
+
+// A subset of the RFC 1345 digraphs Vim uses
+const DIGRAPHS: &[(&str, char)] = &[
+    // Typography
+    ("-N", '–'),
+    ("-M", '—'),
+    ("..", '‥'),
+    (",.", '…'),
+    ("'6", '‘'),
+    ("'9", '’'),
+    ("\"6", '“'),
+    ("\"9", '”'),
+    ("<<", '«'),
+    (">>", '»'),
+    ("SE", '§'),
+    ("PI", '¶'),
+    ("/-", '†'),
+    ("/=", '‡'),
+    ("Co", '©'),
+    ("Rg", '®'),
+    ("TM", '™'),
+    ("DG", '°'),
+    ("Eu", '€'),
+    ("Pd", '£'),
+    ("Ye", '¥'),
+    ("Ct", '¢'),
+    (".M", '·'),
+    ("NS", '\u{a0}'),
+    // Arrows
+    ("<-", '←'),
+    ("->", '→'),
+    ("-!", '↑'),
+    ("-v", '↓'),
+    ("<>", '↔'),
+    ("=>", '⇒'),
+    ("==", '⇔'),
+    // Mathematics
+    ("+-", '±'),
+    ("*X", '×'),
+    ("-:", '÷'),
+    ("!=", '≠'),
+    ("=<", '≤'),
+    (">=", '≥'),
+    ("?=", '≅'),
+    ("?2", '≈'),
+    ("=3", '≡'),
+    ("00", '∞'),
+    ("RT", '√'),
+    ("FA", '∀'),
+    ("dP", '∂'),
+    ("TE", '∃'),
+    ("/0", '∅'),
+    ("DE", '∆'),
+    ("NB", '∇'),
+    ("(-", '∈'),
+    ("*P", '∏'),
+    ("+Z", '∑'),
+    ("In", '∫'),
+    ("AN", '∧'),
+    ("OR", '∨'),
+    ("(U", '∩'),
+    (")U", '∪'),
+    ("(C", '⊂'),
+    (")C", '⊃'),
+    ("(_", '⊆'),
+    (")_", '⊇'),
+    ("NO", '¬'),
+    ("1S", '¹'),
+    ("2S", '²'),
+    ("3S", '³'),
+    ("12", '½'),
+    ("14", '¼'),
+    ("34", '¾'),
+    // Greek
+    ("a*", 'α'),
+    ("b*", 'β'),
+    ("g*", 'γ'),
+    ("d*", 'δ'),
+    ("e*", 'ε'),
+    ("z*", 'ζ'),
+    ("y*", 'η'),
+    ("h*", 'θ'),
+    ("i*", 'ι'),
+    ("k*", 'κ'),
+    ("l*", 'λ'),
+    ("m*", 'μ'),
+    ("n*", 'ν'),
+    ("c*", 'ξ'),
+    ("p*", 'π'),
+    ("r*", 'ρ'),
+    ("s*", 'σ'),
+    ("t*", 'τ'),
+    ("f*", 'φ'),
+    ("x*", 'χ'),
+    ("q*", 'ψ'),
+    ("w*", 'ω'),
+    ("G*", 'Γ'),
+    ("D*", 'Δ'),
+    ("H*", 'Θ'),
+    ("L*", 'Λ'),
+    ("P*", 'Π'),
+    ("S*", 'Σ'),
+    ("F*", 'Φ'),
+    ("Q*", 'Ψ'),
+    ("W*", 'Ω'),
+    // Latin letters
+    ("a:", 'ä'),
+    ("o:", 'ö'),
+    ("u:", 'ü'),
+    ("A:", 'Ä'),
+    ("O:", 'Ö'),
+    ("U:", 'Ü'),
+    ("ss", 'ß'),
+    ("e'", 'é'),
+    ("e!", 'è'),
+    ("a!", 'à'),
+    ("c,", 'ç'),
+    ("n?", 'ñ'),
+];
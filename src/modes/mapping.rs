@@ -0,0 +1,125 @@
+use anyhow::{anyhow, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use super::Mode;
+
+/// Recursive mappings stop expanding after this many levels
+pub const MAX_EXPANSION_DEPTH: usize = 100;
+
+/// A key as mappings see it. Shift is folded into the character itself.
+pub type MapKey = (KeyCode, KeyModifiers);
+
+pub fn map_key(key: KeyEvent) -> MapKey {
+    let modifiers = match key.code {
+        KeyCode::Char(_) => key.modifiers - KeyModifiers::SHIFT,
+        _ => key.modifiers,
+    };
+    (key.code, modifiers)
+}
+
+/// A user mapping defined with `:map` and friends
+#[derive(Debug, Clone)]
+pub struct CompiledKeymap {
+    pub modes: &'static [Mode],
+    pub lhs: Vec<MapKey>,
+    pub rhs: Vec<MapKey>,
+    /// `:map` feeds the `rhs` back through the mappings, `:noremap` doesn't
+    pub recursive: bool,
+}
+
+impl CompiledKeymap {
+    pub fn applies_in(&self, mode: Mode) -> bool {
+        self.modes.contains(&mode)
+    }
+}
+
+/// Add `map` to `keymaps`, replacing a mapping of the same keys in the
+/// same modes
+pub fn add_keymap(keymaps: &mut Vec<CompiledKeymap>, map: CompiledKeymap) {
+    keymaps.retain(|existing| existing.lhs != map.lhs || existing.modes != map.modes);
+    keymaps.push(map);
+}
+
+/// Parse a mapping command (`:map`, `:nmap`, `:imap`, `:noremap`, `:nnoremap`,
+/// `:inoremap`). Returns `None` if `command` isn't one.
+pub fn parse_map_command(command: &str) -> Option<Result<CompiledKeymap>> {
+    let mut parts = command.splitn(3, char::is_whitespace);
+    let name = parts.next()?;
+    let (modes, recursive): (&'static [Mode], bool) = match name {
+        "map" => (&[Mode::Normal, Mode::Visual], true),
+        "nmap" => (&[Mode::Normal], true),
+        "imap" => (&[Mode::Insert], true),
+        "noremap" => (&[Mode::Normal, Mode::Visual], false),
+        "nnoremap" => (&[Mode::Normal], false),
+        "inoremap" => (&[Mode::Insert], false),
+        _ => return None,
+    };
+
+    let lhs = parts.next().unwrap_or("");
+    let rhs = parts.next().unwrap_or("").trim();
+    if lhs.is_empty() || rhs.is_empty() {
+        return Some(Err(anyhow!("E474: Invalid argument")));
+    }
+
+    Some(parse_keys(lhs).and_then(|lhs| {
+        Ok(CompiledKeymap {
+            modes,
+            lhs,
+            rhs: parse_keys(rhs)?,
+            recursive,
+        })
+    }))
+}
+
+/// Parse Vim key notation like `jk`, `<Esc>`, `:w<CR>` or `<C-s>`
+pub fn parse_keys(notation: &str) -> Result<Vec<MapKey>> {
+    let mut keys = Vec::new();
+    let mut rest = notation;
+    while let Some(c) = rest.chars().next() {
+        let special = if c == '<' { rest.find('>').map(|end| &rest[1..end]) } else { None };
+        match special.and_then(parse_special_key) {
+            Some(key) => {
+                rest = &rest[special.map_or(0, |name| name.len()) + 2..];
+                keys.push(key);
+            }
+            None => {
+                rest = &rest[c.len_utf8()..];
+                keys.push((KeyCode::Char(c), KeyModifiers::NONE));
+            }
+        }
+    }
+
+    if keys.is_empty() {
+        return Err(anyhow!("E474: Invalid argument"));
+    }
+    Ok(keys)
+}
+
+// The key named inside `<...>`, if it is one we know
+fn parse_special_key(name: &str) -> Option<MapKey> {
+    let lower = name.to_lowercase();
+    if let Some(c) = lower.strip_prefix("c-") {
+        let mut chars = c.chars();
+        return match (chars.next(), chars.next()) {
+            (Some(c), None) => Some((KeyCode::Char(c), KeyModifiers::CONTROL)),
+            _ => None,
+        };
+    }
+
+    let code = match lower.as_str() {
+        "esc" => KeyCode::Esc,
+        "cr" | "enter" | "return" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "bs" => KeyCode::Backspace,
+        "del" => KeyCode::Delete,
+        "space" => KeyCode::Char(' '),
+        "lt" => KeyCode::Char('<'),
+        "bar" => KeyCode::Char('|'),
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        _ => return None,
+    };
+    Some((code, KeyModifiers::NONE))
+}
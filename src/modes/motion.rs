@@ -0,0 +1,199 @@
+use crossterm::event::KeyCode;
+
+use crate::config::Config;
+use crate::core::buffer::Buffer;
+use crate::core::cursor::Cursor;
+use crate::core::Position;
+
+/// A motion an operator like `d` or `y` can act on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordForward,
+    BigWordForward,
+    WordBackward,
+    BigWordBackward,
+    WordEnd,
+    /// `w` after `c` on a word, which changes up to the end of the word
+    /// like `e` does, leaving the space after it
+    ChangeWord,
+    LineStart,
+    LineEnd,
+    FileStart,
+    FileEnd,
+}
+
+impl Motion {
+    /// The motion a key typed after an operator stands for, like the `w` of
+    /// `dw`. `FileStart` takes two keys (`gg`) and isn't returned here.
+    pub fn from_key(code: KeyCode) -> Option<Self> {
+        Some(match code {
+            KeyCode::Char('h') | KeyCode::Left => Self::Left,
+            KeyCode::Char('l') | KeyCode::Right => Self::Right,
+            KeyCode::Char('k') | KeyCode::Up => Self::Up,
+            KeyCode::Char('j') | KeyCode::Down => Self::Down,
+            KeyCode::Char('w') => Self::WordForward,
+            KeyCode::Char('W') => Self::BigWordForward,
+            KeyCode::Char('b') => Self::WordBackward,
+            KeyCode::Char('B') => Self::BigWordBackward,
+            // There is no separate end of WORD motion yet
+            KeyCode::Char('e') | KeyCode::Char('E') => Self::WordEnd,
+            KeyCode::Char('0') => Self::LineStart,
+            KeyCode::Char('$') => Self::LineEnd,
+            KeyCode::Char('G') => Self::FileEnd,
+            _ => return None,
+        })
+    }
+
+    /// Operators on these act on whole lines
+    pub fn is_linewise(self) -> bool {
+        matches!(self, Self::Up | Self::Down | Self::FileStart | Self::FileEnd)
+    }
+
+    /// Operators on these include the character the motion ends on
+    pub fn is_inclusive(self) -> bool {
+        matches!(self, Self::WordEnd | Self::ChangeWord)
+    }
+
+    /// Where the motion takes a cursor at `from`
+    pub fn target(self, from: Position, content: &[String]) -> Position {
+        let mut cursor = Cursor::new();
+        cursor.move_to_position(from);
+        match self {
+            Self::Left => cursor.move_left(content),
+            Self::Right => cursor.move_right(content),
+            Self::Up => cursor.move_up(content),
+            Self::Down => cursor.move_down(content),
+            Self::WordForward => cursor.move_word_forward(content),
+            Self::BigWordForward => cursor.move_word_forward_whitespace(content),
+            Self::WordBackward => cursor.move_word_backward(content),
+            Self::BigWordBackward => cursor.move_word_backward_whitespace(content),
+            Self::WordEnd | Self::ChangeWord => cursor.move_to_end_of_word(content),
+            Self::LineStart => cursor.move_to_column(0),
+            Self::LineEnd => {
+                let len = content.get(from.row).map_or(0, |line| line.chars().count());
+                cursor.move_to_column(len);
+            }
+            Self::FileStart => cursor.move_to_position(Position { row: 0, col: 0 }),
+            Self::FileEnd => cursor.move_to_position(Position { row: content.len().saturating_sub(1), col: 0 }),
+        }
+        cursor.position()
+    }
+}
+
+/// The text an operator works on: from `start` up to `end` (exclusive), or
+/// with `linewise` the whole rows `start.row..=end.row`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangeResult {
+    pub start: Position,
+    pub end: Position,
+    pub linewise: bool,
+}
+
+/// The text `motion`, done `count` times from `anchor`, moves over, for an
+/// operator to work on. `None` if it doesn't move over anything.
+pub fn compute_range_for_motion(
+    motion: Motion,
+    count: usize,
+    anchor: Position,
+    buffer: &Buffer,
+    config: &Config,
+) -> Option<RangeResult> {
+    let content = &buffer.content;
+    let line_len = |row: usize| content.get(row).map_or(0, |line| line.chars().count());
+    let count = count.max(1);
+    
+    let target = match motion {
+        // `h` and `l` stop at the ends of the line rather than wrapping
+        Motion::Left => Position { row: anchor.row, col: anchor.col.saturating_sub(count) },
+        Motion::Right => Position { row: anchor.row, col: (anchor.col + count).min(line_len(anchor.row)) },
+        // On the last character of a word `cw` changes just that character,
+        // where `e` would run on to the end of the next word
+        Motion::ChangeWord if count == 1 && ends_word(buffer, anchor, &config.ui.iskeyword) => anchor,
+        Motion::WordForward | Motion::BigWordForward => {
+            let mut pos = anchor;
+            for _ in 0..count {
+                let next = motion.target(pos, content);
+                if next == pos {
+                    // The last word of the buffer goes up to its end
+                    pos = Position { row: pos.row, col: line_len(pos.row) };
+                    break;
+                }
+                pos = next;
+            }
+            // A word motion onto a later line stops at the end of the one before
+            let indent_only = content
+                .get(pos.row)
+                .is_some_and(|line| line.chars().take(pos.col).all(char::is_whitespace));
+            if pos.row > anchor.row && indent_only {
+                pos = Position { row: pos.row - 1, col: line_len(pos.row - 1) };
+            }
+            pos
+        }
+        _ => (0..count).fold(anchor, |pos, _| motion.target(pos, content)),
+    };
+    
+    let (start, mut end) = if target < anchor { (target, anchor) } else { (anchor, target) };
+    if motion.is_inclusive() {
+        end.col = (end.col + 1).min(line_len(end.row));
+    }
+    let linewise = motion.is_linewise();
+    if !linewise && start == end {
+        return None;
+    }
+    Some(RangeResult { start, end, linewise })
+}
+
+// Whether `pos` is on the last character of a word, made of letters, digits
+// and the characters in `iskeyword`
+fn ends_word(buffer: &Buffer, pos: Position, iskeyword: &str) -> bool {
+    let chars: Vec<char> = buffer.content[pos.row].chars().collect();
+    let class = |c: char| {
+        if c.is_whitespace() {
+            0
+        } else if c.is_alphanumeric() || iskeyword.contains(c) {
+            1
+        } else {
+            2
+        }
+    };
+    match (chars.get(pos.col), chars.get(pos.col + 1)) {
+        (Some(&c), Some(&next)) => class(c) != 0 && class(c) != class(next),
+        (Some(&c), None) => class(c) != 0,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(line: &str, motion: Motion, col: usize) -> Option<(usize, usize)> {
+        let mut buffer = Buffer::new(0, "motion".to_string());
+        buffer.content = vec![line.to_string()];
+        compute_range_for_motion(motion, 1, Position { row: 0, col }, &buffer, &Config::default())
+            .map(|range| (range.start.col, range.end.col))
+    }
+
+    #[test]
+    fn change_word_stops_at_the_end_of_the_word() {
+        assert_eq!(range("foo bar", Motion::ChangeWord, 0), Some((0, 3)));
+        // On its last character just that character
+        assert_eq!(range("foo bar", Motion::ChangeWord, 2), Some((2, 3)));
+        assert_eq!(range("foo bar", Motion::WordEnd, 2), Some((2, 7)));
+    }
+
+    #[test]
+    fn change_word_takes_iskeyword_characters_as_part_of_the_word() {
+        assert_eq!(range("foo_bar baz", Motion::ChangeWord, 2), Some((2, 7)));
+    }
+
+    #[test]
+    fn left_and_right_stop_at_the_line_ends() {
+        assert_eq!(range("ab", Motion::Left, 0), None);
+        assert_eq!(range("ab", Motion::Right, 1), Some((1, 2)));
+    }
+}
@@ -0,0 +1,74 @@
+use anyhow::{anyhow, Result};
+
+use crate::config::Config;
+
+/// The boolean options `:set` can change, by name and short name
+const BOOLEAN_OPTIONS: &[(&str, &str)] = &[
+    ("number", "nu"),
+    ("wrap", "wrap"),
+    ("list", "list"),
+    ("spell", "spell"),
+    ("paste", "paste"),
+    ("expandtab", "et"),
+    ("breakindent", "bri"),
+];
+
+// The config field behind the option called `name`, and its full name
+fn option_mut<'a>(config: &'a mut Config, name: &str) -> Option<(&'static str, &'a mut bool)> {
+    let (long, _) = BOOLEAN_OPTIONS.iter().find(|(long, short)| name == *long || name == *short)?;
+    let value = match *long {
+        "number" => &mut config.ui.show_line_numbers,
+        "wrap" => &mut config.ui.wrap_lines,
+        "list" => &mut config.ui.list,
+        "spell" => &mut config.ui.spell,
+        "paste" => &mut config.ui.paste,
+        "expandtab" => &mut config.editor.expandtab,
+        "breakindent" => &mut config.ui.break_indent,
+        _ => return None,
+    };
+    Some((long, value))
+}
+
+/// Apply the arguments of a `:set` command to `config`. Each is `{option}`,
+/// `no{option}`, `inv{option}` or `{option}!` to toggle, or `{option}?` to
+/// show its value. With no arguments, every option is shown. Returns what
+/// to show, if anything.
+pub fn set(config: &mut Config, args: &str) -> Result<Option<String>> {
+    let args: Vec<&str> = args.split_whitespace().collect();
+    if args.is_empty() {
+        let all: Vec<String> = BOOLEAN_OPTIONS
+            .iter()
+            .filter_map(|(name, _)| option_mut(config, name).map(|(name, value)| shown(name, *value)))
+            .collect();
+        return Ok(Some(all.join("  ")));
+    }
+
+    let mut queried = Vec::new();
+    for arg in args {
+        let unknown = || anyhow!("E518: Unknown option: {}", arg);
+        if let Some(name) = arg.strip_suffix('?') {
+            let (name, value) = option_mut(config, name).ok_or_else(unknown)?;
+            queried.push(shown(name, *value));
+        } else if let Some((_, value)) = arg.strip_suffix('!').and_then(|name| option_mut(config, name)) {
+            *value = !*value;
+        } else if let Some((_, value)) = arg.strip_prefix("inv").and_then(|name| option_mut(config, name)) {
+            *value = !*value;
+        } else if let Some((_, value)) = arg.strip_prefix("no").and_then(|name| option_mut(config, name)) {
+            *value = false;
+        } else if arg.contains('=') {
+            return Err(anyhow!("E474: Invalid argument: {}", arg));
+        } else {
+            *option_mut(config, arg).ok_or_else(unknown)?.1 = true;
+        }
+    }
+    Ok(Some(queried.join("  ")).filter(|message| !message.is_empty()))
+}
+
+// `list` or `nolist`, as `:set list?` shows it
+fn shown(name: &str, value: bool) -> String {
+    if value {
+        format!("  {}", name)
+    } else {
+        format!("no{}", name)
+    }
+}
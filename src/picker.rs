@@ -1,29 +1,77 @@
 use anyhow::Result;
-use crossterm::event::{KeyCode, KeyEvent};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use ignore::WalkBuilder;
 use ratatui::{
-    layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    widgets::{block::Title, Block, Borders, Cell, List, ListItem, ListState, Paragraph, Row, Table, TableState},
     Frame,
 };
-// use regex::Regex;
-use std::path::PathBuf;
-use std::process::Command;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use std::time::SystemTime;
 
-use crate::config::Config;
-use crate::core::BufferManager;
+use crate::config::{self, Config, PickerConfig, PickerRoot};
+use crate::core::buffer::Buffer;
+use crate::core::outline::{self, OutlineParser};
+use crate::core::{project, BufferManager, Position};
+use crate::modes::keymap::{EX_COMMANDS, INSERT_KEYS, LEADER_KEYS, NORMAL_KEYS};
 
 pub struct PickerResult {
     pub selected_file: Option<PathBuf>,
     pub selected_buffer_id: Option<usize>,
+    /// A word to search the current buffer for
+    pub search_word: Option<String>,
+    /// Jump to the word's first occurrence instead of the next one after the cursor
+    pub search_from_start: bool,
+    /// A row of the current buffer to jump to
+    pub selected_line: Option<usize>,
+    /// A word to put in place of the one under the cursor
+    pub replacement: Option<String>,
+}
+
+/// What the app should do after the picker handled a key
+pub enum PickerAction {
+    /// Keep the picker open
+    Continue,
+    Cancel,
+    Select(PickerResult),
+    /// Close this buffer and keep the (refreshed) buffer picker open
+    DeleteBuffer(usize),
 }
 
 pub enum PickerType {
     Files,
+    #[allow(dead_code)]
     Grep(String),
     Buffers,
+    Help,
+    Messages,
+    WordFrequency,
+    BufferLines,
+    Outline,
+    Spelling,
+}
+
+impl PickerType {
+    pub fn title(&self) -> &'static str {
+        match self {
+            PickerType::Files => "Find Files",
+            PickerType::Grep(_) => "Grep",
+            PickerType::Buffers => "Buffers",
+            PickerType::Help => "Help",
+            PickerType::Messages => "Messages",
+            PickerType::WordFrequency => "Word Frequency",
+            PickerType::BufferLines => "Buffer Lines",
+            PickerType::Outline => "Outline",
+            PickerType::Spelling => "Spelling Suggestions",
+        }
+    }
 }
 
 pub struct Picker {
@@ -33,122 +81,644 @@ pub struct Picker {
     list_state: ListState,
     input: String,
     show_preview: bool,
+    // Prefix file entries with a type icon
+    show_icons: bool,
+    regex_mode: bool,
+    invalid_regex: bool,
+    // Set for the file picker, whose list Tab completion rescopes
+    file_walk: Option<FileWalk>,
+    // Candidates cycled by repeated Tab presses, and the one shown
+    completions: Option<(Vec<String>, Option<usize>)>,
+    // Shown in the title until the next key, e.g. why a buffer wasn't closed
+    notice: Option<String>,
+    // Grep stops reading matches after this many
+    max_results: usize,
+    // Queries that led to a selection in this kind of picker, newest first
+    history: VecDeque<String>,
+    // The history entry `<C-P>` last put into the input, until the query
+    // is edited and the next `<C-P>` starts at the newest entry again
+    history_index: Option<usize>,
 }
 
-#[derive(Debug, Clone)]
+// Where the file picker's list comes from
+struct FileWalk {
+    root: PathBuf,
+    // Directory under `root` (ending in `/`) the list is limited to, or empty
+    scope: String,
+    config: PickerConfig,
+}
+
+#[derive(Debug, Clone, Default)]
 struct PickerItem {
     display: String,
     path: Option<PathBuf>,
     buffer_id: Option<usize>,
     line_number: Option<usize>,
     match_text: Option<String>,
+    // Ranking hints: shallower paths and files at the project root win ties
+    depth_score: i32,
+    project_root_bonus: i32,
+    // Shown in the buffer picker
+    last_modified: Option<SystemTime>,
+    cursor: Option<Position>,
+    file_size: Option<u64>,
+    // Set for the recent searches leading the list; selecting one types it
+    history_query: Option<String>,
 }
 
-impl Picker {
-    pub async fn new_file_picker(config: &Config) -> Result<Self> {
-        let mut items = Vec::new();
-        let current_dir = std::env::current_dir()?;
-        
-        let walker = WalkBuilder::new(&current_dir)
-            .hidden(false)
-            .git_ignore(true)
-            .build();
-        
-        for entry in walker {
-            if let Ok(entry) = entry {
-                if entry.file_type().map(|ft| ft.is_file()).unwrap_or(false) {
-                    let path = entry.path().to_path_buf();
-                    let display = path
-                        .strip_prefix(&current_dir)
-                        .unwrap_or(&path)
-                        .to_string_lossy()
-                        .to_string();
-                    
-                    // Filter by ignore patterns
-                    if !config.picker.file_ignore_patterns.iter().any(|pattern| {
-                        display.contains(pattern) || path.to_string_lossy().contains(pattern)
-                    }) {
-                        items.push(PickerItem {
-                            display,
-                            path: Some(path),
-                            buffer_id: None,
-                            line_number: None,
-                            match_text: None,
-                        });
-                    }
-                }
-            }
+impl PickerItem {
+    fn new(display: String) -> Self {
+        Self { display, ..Default::default() }
+    }
+}
+
+// Recent searches listed when a picker opens, and kept per picker
+const HISTORY_SHOWN: usize = 5;
+const HISTORY_KEPT: usize = 50;
+
+/// Queries that led to a selection, per picker title, saved across sessions
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PickerHistory {
+    entries: HashMap<String, VecDeque<String>>,
+}
+
+impl PickerHistory {
+    fn path() -> PathBuf {
+        config::config_dir().join("picker_history.json")
+    }
+    
+    /// The saved history, or an empty one if there is none or it can't be read
+    pub fn load() -> Self {
+        std::fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+    
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+    
+    /// The queries of the picker titled `title`, newest first
+    pub fn get(&self, title: &str) -> VecDeque<String> {
+        self.entries.get(title).cloned().unwrap_or_default()
+    }
+    
+    /// Record `query` as the newest entry for `title`, dropping any older copy
+    pub fn push(&mut self, title: &str, query: &str) {
+        let entries = self.entries.entry(title.to_string()).or_default();
+        entries.retain(|entry| entry != query);
+        entries.push_front(query.to_string());
+        entries.truncate(HISTORY_KEPT);
+    }
+}
+
+fn history_item(query: &str) -> PickerItem {
+    PickerItem {
+        display: format!("[history] {}", query),
+        history_query: Some(query.to_string()),
+        ..Default::default()
+    }
+}
+
+// Extra score for files sitting directly in the project root
+const PROJECT_ROOT_BONUS: i32 = 5;
+
+// Narrower than this and the preview is dropped to keep the list readable
+const MIN_PREVIEW_WIDTH: u16 = 80;
+
+/// The files under `dir` (shown relative to `root`) that `config` doesn't
+/// ignore or consider too large, up to `max_results`. With
+/// `show_directories`, the directories too. Inside a git work tree the
+/// files come from `git ls-files` when `use_git_files` allows it.
+fn collect_files(dir: &Path, root: &Path, config: &PickerConfig) -> Vec<PickerItem> {
+    let mut items = Vec::new();
+    let project_root = project::find_root(root, project::ROOT_MARKERS);
+    
+    // git only knows files, and nothing about what it ignores
+    let git_files = (config.use_git_files && config.respect_gitignore && !config.show_directories)
+        .then(|| git_files(dir, config.include_untracked))
+        .flatten();
+    let candidates: Box<dyn Iterator<Item = (PathBuf, bool)>> = match git_files {
+        Some(files) => Box::new(
+            files
+                .into_iter()
+                .filter(|path| config.show_hidden || !is_hidden(path.strip_prefix(dir).unwrap_or(path)))
+                .filter(|path| {
+                    // The index still lists files deleted from the work tree
+                    std::fs::metadata(path).is_ok_and(|metadata| metadata.is_file() && metadata.len() <= config.max_file_size_bytes)
+                })
+                .map(|path| (path, false)),
+        ),
+        None => {
+            let walker = WalkBuilder::new(dir)
+                .hidden(!config.show_hidden)
+                .git_ignore(config.respect_gitignore)
+                .git_exclude(config.respect_gitignore)
+                .git_global(config.respect_gitignore)
+                .build();
+            Box::new(walker.flatten().filter_map(|entry| {
+                let too_large = entry
+                    .metadata()
+                    .is_ok_and(|metadata| metadata.len() > config.max_file_size_bytes);
+                // Directories (not the walk's own) end in `/`, so selecting one
+                // completes the input into it
+                let is_dir = config.show_directories
+                    && entry.depth() > 0
+                    && entry.file_type().is_some_and(|ft| ft.is_dir());
+                let is_file = entry.file_type().map(|ft| ft.is_file()).unwrap_or(false) && !too_large;
+                (is_file || is_dir).then(|| (entry.into_path(), is_dir))
+            }))
+        }
+    };
+    
+    for (path, is_dir) in candidates {
+        if items.len() == config.max_results {
+            break;
+        }
+        let mut display = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .to_string();
+        if is_dir {
+            display.push('/');
         }
         
-        // Limit results
-        items.truncate(config.picker.max_results);
-        let filtered_items: Vec<usize> = (0..items.len()).collect();
+        // Filter by ignore patterns
+        if !config.file_ignore_patterns.iter().any(|pattern| {
+            display.contains(pattern) || path.to_string_lossy().contains(pattern)
+        }) {
+            let in_project_root = project_root.as_deref() == path.parent();
+            items.push(PickerItem {
+                depth_score: depth_score(Path::new(&display)),
+                project_root_bonus: if in_project_root { PROJECT_ROOT_BONUS } else { 0 },
+                display,
+                path: Some(path),
+                ..Default::default()
+            });
+        }
+    }
+    
+    items
+}
+
+/// The files under `dir` that git tracks and, with `include_untracked`, the
+/// untracked ones it doesn't ignore. None outside a git work tree, or if
+/// git can't be run.
+fn git_files(dir: &Path, include_untracked: bool) -> Option<Vec<PathBuf>> {
+    if !dir.ancestors().any(|dir| dir.join(".git").exists()) {
+        return None;
+    }
+    let mut command = Command::new("git");
+    command.arg("-C").arg(dir).args(["ls-files", "-z", "--cached"]);
+    if include_untracked {
+        command.args(["--others", "--exclude-standard"]);
+    }
+    let output = command.stderr(Stdio::null()).output().ok().filter(|output| output.status.success())?;
+    Some(
+        output
+            .stdout
+            .split(|&byte| byte == 0)
+            .filter(|name| !name.is_empty())
+            .map(|name| dir.join(String::from_utf8_lossy(name).as_ref()))
+            .collect(),
+    )
+}
+
+// Whether any part of `relative` is a dotfile, as the walk would skip it
+fn is_hidden(relative: &Path) -> bool {
+    relative
+        .components()
+        .any(|component| component.as_os_str().to_string_lossy().starts_with('.'))
+}
+
+/// Completes paths typed into the file picker, relative to `base`
+pub struct PathCompleter {
+    base: PathBuf,
+}
+
+impl PathCompleter {
+    pub fn new(base: PathBuf) -> Self {
+        Self { base }
+    }
+    
+    /// The entries of the directory `partial` points into whose names start
+    /// with its last component, sorted. Directories end with `/`. Hidden
+    /// entries are only offered once a `.` has been typed.
+    pub fn complete(&self, partial: &str) -> Vec<String> {
+        let (dir, prefix) = match partial.rfind('/') {
+            Some(slash) => partial.split_at(slash + 1),
+            None => ("", partial),
+        };
+        let Ok(entries) = std::fs::read_dir(self.base.join(dir)) else {
+            return Vec::new();
+        };
         
+        let mut completions: Vec<String> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                    return None;
+                }
+                let slash = if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) { "/" } else { "" };
+                Some(format!("{}{}{}", dir, name, slash))
+            })
+            .collect();
+        completions.sort();
+        completions
+    }
+}
+
+// The longest string all of `strings` start with
+fn longest_common_prefix(strings: &[String]) -> &str {
+    let Some(first) = strings.first() else {
+        return "";
+    };
+    let mut len = first.len();
+    for string in &strings[1..] {
+        len = first
+            .char_indices()
+            .zip(string.chars())
+            .take_while(|((i, a), b)| *i < len && a == b)
+            .last()
+            .map_or(0, |((i, a), _)| i + a.len_utf8());
+    }
+    &first[..len]
+}
+
+// The item's display text, after its file icon if `show_icons` is on
+fn item_line<'a>(item: &'a PickerItem, show_icons: bool, highlight: Option<&Regex>) -> Line<'a> {
+    let mut spans = Vec::new();
+    if let Some(path) = item.path.as_deref().filter(|_| show_icons) {
+        let (icon, color) = file_icon(path);
+        spans.push(Span::styled(format!("{} ", icon), Style::default().fg(color)));
+    }
+    
+    // Grep entries end with their matched line; pick out the matches in it
+    match (highlight, item.match_text.as_deref()) {
+        (Some(regex), Some(text)) if item.display.ends_with(text) => {
+            let offset = item.display.len() - text.len();
+            let mut last = 0;
+            for found in regex.find_iter(text).filter(|found| !found.is_empty()) {
+                spans.push(Span::raw(&item.display[last..offset + found.start()]));
+                spans.push(Span::styled(
+                    &item.display[offset + found.start()..offset + found.end()],
+                    Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+                ));
+                last = offset + found.end();
+            }
+            spans.push(Span::raw(&item.display[last..]));
+        }
+        _ if item.history_query.is_some() => {
+            spans.push(Span::styled(item.display.as_str(), Style::default().fg(Color::DarkGray)));
+        }
+        _ => spans.push(Span::raw(item.display.as_str())),
+    }
+    Line::from(spans)
+}
+
+/// A Nerd Font glyph and color for the type of file at `path`
+fn file_icon(path: &Path) -> (&'static str, Color) {
+    let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or("");
+    match extension {
+        "rs" => ("\u{e7a8}", Color::LightRed),
+        "py" => ("\u{e73c}", Color::Yellow),
+        "js" | "jsx" => ("\u{e74e}", Color::Yellow),
+        "ts" | "tsx" => ("\u{e628}", Color::Blue),
+        "go" => ("\u{e626}", Color::Cyan),
+        "c" | "h" | "cpp" | "hpp" | "cc" => ("\u{e61e}", Color::Blue),
+        "md" => ("\u{e609}", Color::White),
+        "toml" | "yaml" | "yml" | "json" => ("\u{e615}", Color::Gray),
+        "sh" | "bash" | "zsh" => ("\u{f489}", Color::Green),
+        "html" => ("\u{e736}", Color::LightRed),
+        "css" => ("\u{e749}", Color::Blue),
+        "lock" => ("\u{f023}", Color::DarkGray),
+        _ => ("\u{f15b}", Color::Gray),
+    }
+}
+
+/// How long ago `time` was, e.g. `5m ago`
+fn format_age(time: SystemTime) -> String {
+    let seconds = time.elapsed().map(|age| age.as_secs()).unwrap_or(0);
+    match seconds {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86399 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
+/// A file size in human-readable form, e.g. `1.2 KB`
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Split a `file:line:text` line of grep output. A Windows drive letter
+/// (`C:\src\main.rs:10:text`) stays part of the file name.
+fn parse_grep_line(line: &str) -> Option<(&str, usize, &str)> {
+    let drive_len = match line.as_bytes() {
+        [letter, b':', b'\\' | b'/', ..] if letter.is_ascii_alphabetic() => 2,
+        _ => 0,
+    };
+    let (file, rest) = line[drive_len..].split_once(':')?;
+    let (line_number, text) = rest.split_once(':')?;
+    Some((&line[..drive_len + file.len()], line_number.parse().ok()?, text))
+}
+
+/// A grep picker entry for `content` on 1-based line `line_num` of `file`
+fn grep_item(file: &str, line_num: usize, content: &str) -> PickerItem {
+    PickerItem {
+        display: format!("{}:{}: {}", file, line_num, content),
+        path: Some(PathBuf::from(file)),
+        line_number: Some(line_num),
+        match_text: Some(content.to_string()),
+        ..Default::default()
+    }
+}
+
+/// Grep without an external tool: the lines matching `regex` in the files
+/// under `dir` that aren't hidden or ignored, up to `max_results`
+fn search_files(dir: &Path, regex: &Regex, max_results: usize) -> Vec<PickerItem> {
+    let mut items = Vec::new();
+    let files = WalkBuilder::new(dir)
+        .build()
+        .flatten()
+        .filter(|entry| entry.file_type().is_some_and(|ft| ft.is_file()));
+    
+    for entry in files {
+        // Binary and non-UTF-8 files don't read as strings and are skipped
+        let Ok(content) = std::fs::read_to_string(entry.path()) else {
+            continue;
+        };
+        let file = entry.path().strip_prefix(dir).unwrap_or(entry.path()).to_string_lossy();
+        for (row, line) in content.lines().enumerate() {
+            if regex.is_match(line) {
+                items.push(grep_item(&file, row + 1, line));
+                if items.len() >= max_results {
+                    return items;
+                }
+            }
+        }
+    }
+    items
+}
+
+/// Shallower paths score higher
+fn depth_score(path: &Path) -> i32 {
+    -(path.components().count() as i32)
+}
+
+/// Case-insensitive subsequence match of `query` against `candidate`.
+/// Consecutive matches and matches at word boundaries score extra.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut last_match: Option<usize> = None;
+    
+    for qc in query.to_lowercase().chars() {
+        let idx = (next..candidate.len()).find(|&i| candidate[i] == qc)?;
+        score += 1;
+        if last_match.is_some_and(|last| last + 1 == idx) {
+            score += 2;
+        }
+        if idx == 0 || matches!(candidate[idx - 1], '/' | '\\' | '_' | '-' | '.' | ' ') {
+            score += 2;
+        }
+        last_match = Some(idx);
+        next = idx + 1;
+    }
+    
+    Some(score)
+}
+
+impl Picker {
+    /// A picker listing `items`, first one selected, with an empty query
+    /// and the preview, icons and regex search off
+    fn with_items(picker_type: PickerType, items: Vec<PickerItem>) -> Self {
         let mut list_state = ListState::default();
         if !items.is_empty() {
             list_state.select(Some(0));
         }
         
-        Ok(Self {
-            picker_type: PickerType::Files,
+        Self {
+            picker_type,
+            filtered_items: (0..items.len()).collect(),
             items,
-            filtered_items,
             list_state,
             input: String::new(),
-            show_preview: config.picker.preview_enabled,
+            show_preview: false,
+            show_icons: false,
+            regex_mode: false,
+            invalid_regex: false,
+            file_walk: None,
+            completions: None,
+            notice: None,
+            max_results: PickerConfig::default().max_results,
+            history: VecDeque::new(),
+            history_index: None,
+        }
+    }
+    
+    // Take the preview, icons, regex search and result limit from `[picker]`
+    fn with_config(self, config: &PickerConfig) -> Self {
+        Self {
+            show_preview: config.preview_enabled,
+            show_icons: config.show_icons,
+            regex_mode: config.grep_regex,
+            max_results: config.max_results,
+            ..self
+        }
+    }
+    
+    /// List the files under `root`, or under the directory `picker.root`
+    /// picks when it is `None`
+    pub async fn new_file_picker(config: &Config, root: Option<PathBuf>) -> Result<Self> {
+        let current_dir = match root {
+            Some(root) => root,
+            None => {
+                let cwd = std::env::current_dir()?;
+                match config.picker.root {
+                    PickerRoot::Cwd => cwd,
+                    PickerRoot::Git => project::find_root(&cwd, &[".git"]).unwrap_or(cwd),
+                }
+            }
+        };
+        let items = collect_files(&current_dir, &current_dir, &config.picker);
+        
+        Ok(Self {
+            file_walk: Some(FileWalk {
+                root: current_dir,
+                scope: String::new(),
+                config: config.picker.clone(),
+            }),
+            ..Self::with_items(PickerType::Files, items).with_config(&config.picker)
         })
     }
     
     pub async fn new_grep_picker(config: &Config) -> Result<Self> {
         // Start with empty items, will be populated when user types
-        Ok(Self {
-            picker_type: PickerType::Grep(String::new()),
-            items: Vec::new(),
-            filtered_items: Vec::new(),
-            list_state: ListState::default(),
-            input: String::new(),
-            show_preview: config.picker.preview_enabled,
-        })
+        Ok(Self::with_items(PickerType::Grep(String::new()), Vec::new()).with_config(&config.picker))
     }
     
     pub async fn new_buffer_picker(config: &Config, buffer_manager: &BufferManager) -> Result<Self> {
         let mut items = Vec::new();
         
         for buffer in buffer_manager.list_buffers() {
-            let display = if buffer.modified {
-                format!("{} [+]", buffer.name)
+            // `%` marks the current buffer and `#` the alternate one, as in `:ls`
+            let sigil = if buffer_manager.current_buffer_id() == Some(buffer.id) {
+                '%'
+            } else if buffer_manager.alternate_buffer_id() == Some(buffer.id) {
+                '#'
             } else {
-                buffer.name.clone()
+                ' '
             };
+            let modified = if buffer.modified { " [+]" } else { "" };
+            let metadata = buffer.path.as_ref().and_then(|path| std::fs::metadata(path).ok());
             
             items.push(PickerItem {
-                display,
+                display: format!("{:>3} {} {}{}", buffer.id, sigil, buffer.name, modified),
                 path: buffer.path.clone(),
                 buffer_id: Some(buffer.id),
-                line_number: None,
-                match_text: None,
+                depth_score: buffer.path.as_deref().map(depth_score).unwrap_or(0),
+                last_modified: metadata.as_ref().and_then(|metadata| metadata.modified().ok()),
+                cursor: Some(buffer.cursor.position()),
+                file_size: metadata.map(|metadata| metadata.len()),
+                ..Default::default()
             });
         }
         
-        let filtered_items: Vec<usize> = (0..items.len()).collect();
+        Ok(Self::with_items(PickerType::Buffers, items).with_config(&config.picker))
+    }
+    
+    pub fn new_help_picker() -> Self {
+        let sections: [(&str, &[(&str, &str)]); 4] = [
+            ("Normal mode", NORMAL_KEYS),
+            ("Leader", LEADER_KEYS),
+            ("Insert mode", INSERT_KEYS),
+            ("Ex commands", EX_COMMANDS),
+        ];
         
-        let mut list_state = ListState::default();
-        if !items.is_empty() {
-            list_state.select(Some(0));
+        let mut items = Vec::new();
+        for (title, entries) in sections {
+            items.push(PickerItem::new(format!("── {} ──", title)));
+            for (keys, description) in entries {
+                items.push(PickerItem::new(format!("  {:<18}{}", keys, description)));
+            }
         }
         
-        Ok(Self {
-            picker_type: PickerType::Buffers,
-            items,
-            filtered_items,
-            list_state,
-            input: String::new(),
-            show_preview: config.picker.preview_enabled,
-        })
+        Self::with_items(PickerType::Help, items)
+    }
+    
+    pub fn new_messages_picker<'a>(messages: impl IntoIterator<Item = &'a String>) -> Self {
+        let items: Vec<PickerItem> = messages
+            .into_iter()
+            .map(|message| PickerItem::new(message.clone()))
+            .collect();
+        
+        // Start on the most recent message
+        let last = items.len().checked_sub(1);
+        let mut picker = Self::with_items(PickerType::Messages, items);
+        picker.list_state.select(last);
+        picker
+    }
+    
+    pub fn new_word_frequency_picker(buffer: &Buffer) -> Self {
+        // The first spelling seen is what gets searched for, so the search
+        // lands on the word's first occurrence
+        let mut spellings: HashMap<String, &str> = HashMap::new();
+        for word in buffer.words() {
+            spellings.entry(word.to_lowercase()).or_insert(word);
+        }
+        
+        let mut frequencies: Vec<(String, usize)> = buffer.compute_word_frequencies().into_iter().collect();
+        frequencies.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        
+        let items: Vec<PickerItem> = frequencies
+            .into_iter()
+            .map(|(word, count)| PickerItem {
+                display: format!("{:<30}{:>6}", word, count),
+                match_text: spellings.get(&word).map(|spelling| spelling.to_string()),
+                ..Default::default()
+            })
+            .collect();
+        
+        Self::with_items(PickerType::WordFrequency, items)
+    }
+    
+    pub fn new_buffer_lines_picker(buffer: &Buffer) -> Self {
+        let items: Vec<PickerItem> = buffer
+            .content
+            .iter()
+            .enumerate()
+            .map(|(row, line)| PickerItem {
+                display: format!("{:>4}: {}", row + 1, line),
+                line_number: Some(row + 1),
+                match_text: Some(line.clone()),
+                ..Default::default()
+            })
+            .collect();
+        
+        Self::with_items(PickerType::BufferLines, items)
+    }
+    
+    pub fn new_outline_picker(buffer: &Buffer) -> Self {
+        let filetype = buffer.path.as_deref().and_then(outline::filetype);
+        let entries = filetype.map_or_else(Vec::new, |filetype| OutlineParser::parse(&buffer.content, filetype));
+        
+        let items: Vec<PickerItem> = entries
+            .into_iter()
+            .map(|entry| PickerItem {
+                display: format!("{:>4}: {}{} {}", entry.line + 1, " ".repeat(entry.indent), entry.kind, entry.name),
+                line_number: Some(entry.line + 1),
+                match_text: Some(entry.name),
+                ..Default::default()
+            })
+            .collect();
+        
+        Self {
+            notice: filetype.is_none().then(|| "no outline for this filetype".to_string()),
+            ..Self::with_items(PickerType::Outline, items)
+        }
+    }
+    
+    /// Corrections for `word`, as `z=` lists them
+    pub fn new_spelling_picker(word: &str, suggestions: Vec<String>) -> Self {
+        let items: Vec<PickerItem> = suggestions
+            .into_iter()
+            .enumerate()
+            .map(|(i, suggestion)| PickerItem {
+                display: format!("{:>2}. {}", i + 1, suggestion),
+                match_text: Some(suggestion),
+                ..Default::default()
+            })
+            .collect();
+        
+        Self {
+            notice: Some(format!("change \"{}\" to", word)),
+            ..Self::with_items(PickerType::Spelling, items)
+        }
     }
     
     pub fn render(&mut self, frame: &mut Frame, area: Rect) {
-        let chunks = if self.show_preview {
+        let chunks = if self.show_preview && area.width >= MIN_PREVIEW_WIDTH {
             Layout::default()
                 .direction(Direction::Horizontal)
                 .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
@@ -163,17 +733,52 @@ impl Picker {
             .split(chunks[0]);
         
         // Render input box
+        let mut title = self.picker_type.title().to_string();
+        if let Some(walk) = &self.file_walk {
+            // Breadcrumb of the directory the list is scoped to
+            let root = walk
+                .root
+                .file_name()
+                .map_or_else(|| walk.root.display().to_string(), |name| name.to_string_lossy().into_owned());
+            let crumbs: Vec<&str> = std::iter::once(root.as_str())
+                .chain(walk.scope.split('/').filter(|part| !part.is_empty()))
+                .collect();
+            title.push_str(&format!(": {}", crumbs.join(" › ")));
+            if walk.config.show_hidden {
+                title.push_str(" [hidden]");
+            }
+        }
+        if self.regex_mode {
+            title.push_str(" [regex]");
+        }
+        if self.invalid_regex {
+            title.push_str(" (invalid regex)");
+        }
+        if let Some(notice) = &self.notice {
+            title.push_str(&format!(" ({})", notice));
+        }
+        
+        let input_style = if self.invalid_regex {
+            Style::default().fg(Color::Red)
+        } else {
+            Style::default().fg(Color::White)
+        };
+        
+        // Grep results are the matches themselves; other pickers filter a fixed list
+        let count = match self.picker_type {
+            PickerType::Grep(_) => format!(" {} matches ", self.items.len()),
+            _ => format!(" {}/{} ", self.filtered_items.len(), self.items.len()),
+        };
+        
         let input_block = Block::default()
             .borders(Borders::ALL)
-            .title(match &self.picker_type {
-                PickerType::Files => "Find Files",
-                PickerType::Grep(_) => "Grep",
-                PickerType::Buffers => "Buffers",
-            });
+            .border_style(input_style)
+            .title(title)
+            .title(Title::from(count).alignment(Alignment::Right));
         
         let input_paragraph = Paragraph::new(self.input.as_str())
             .block(input_block)
-            .style(Style::default().fg(Color::White));
+            .style(input_style);
         
         frame.render_widget(input_paragraph, left_chunks[0]);
         
@@ -183,14 +788,28 @@ impl Picker {
             left_chunks[0].y + 1,
         );
         
-        // Render list
+        if matches!(self.picker_type, PickerType::Buffers) {
+            self.render_buffer_table(frame, left_chunks[1]);
+        } else {
+            self.render_list(frame, left_chunks[1]);
+        }
+        
+        // Render preview if enabled
+        if self.show_preview && chunks.len() > 1 {
+            self.render_preview(frame, chunks[1]);
+        }
+    }
+    
+    fn render_list(&mut self, frame: &mut Frame, area: Rect) {
+        let show_icons = self.show_icons;
+        let highlight = self.match_highlight();
         let list_items: Vec<ListItem> = self
             .filtered_items
             .iter()
             .map(|&i| {
                 let item = &self.items[i];
                 let style = Style::default().fg(Color::White);
-                ListItem::new(item.display.clone()).style(style)
+                ListItem::new(item_line(item, show_icons, highlight.as_ref())).style(style)
             })
             .collect();
         
@@ -198,12 +817,56 @@ impl Picker {
             .block(Block::default().borders(Borders::ALL))
             .highlight_style(Style::default().bg(Color::DarkGray));
         
-        frame.render_stateful_widget(list, left_chunks[1], &mut self.list_state);
-        
-        // Render preview if enabled
-        if self.show_preview && chunks.len() > 1 {
-            self.render_preview(frame, chunks[1]);
+        frame.render_stateful_widget(list, area, &mut self.list_state);
+    }
+    
+    // What grep matched, searched for the way the grep itself does
+    fn match_highlight(&self) -> Option<Regex> {
+        if !matches!(self.picker_type, PickerType::Grep(_)) || self.input.is_empty() {
+            return None;
         }
+        let pattern = if self.regex_mode { self.input.clone() } else { regex::escape(&self.input) };
+        Regex::new(&pattern).ok()
+    }
+    
+    /// Buffers as a table: id, `%`/`#` sigil and name on the left; cursor
+    /// position, age and size on the right
+    fn render_buffer_table(&mut self, frame: &mut Frame, area: Rect) {
+        let show_icons = self.show_icons;
+        let rows: Vec<Row> = self
+            .filtered_items
+            .iter()
+            .map(|&i| {
+                let item = &self.items[i];
+                let mut info = Vec::new();
+                if let Some(cursor) = item.cursor {
+                    info.push(format!("{}:{}", cursor.row + 1, cursor.col + 1));
+                }
+                if let Some(modified) = item.last_modified {
+                    info.push(format_age(modified));
+                }
+                if let Some(size) = item.file_size {
+                    info.push(format_size(size));
+                }
+                Row::new(vec![
+                    Cell::from(item_line(item, show_icons, None)),
+                    Cell::from(info.join(" ")).style(Style::default().fg(Color::DarkGray)),
+                ])
+                .style(Style::default().fg(Color::White))
+            })
+            .collect();
+        
+        let widths = [Constraint::Min(30), Constraint::Length(20)];
+        let table = Table::new(rows)
+            .widths(&widths)
+            .block(Block::default().borders(Borders::ALL))
+            .highlight_style(Style::default().bg(Color::DarkGray));
+        
+        let mut table_state = TableState::default()
+            .with_selected(self.list_state.selected())
+            .with_offset(self.list_state.offset());
+        frame.render_stateful_widget(table, area, &mut table_state);
+        *self.list_state.offset_mut() = table_state.offset();
     }
     
     fn render_preview(&self, frame: &mut Frame, area: Rect) {
@@ -248,13 +911,28 @@ impl Picker {
         frame.render_widget(preview, area);
     }
     
-    pub async fn handle_key(&mut self, key: KeyEvent) -> Result<Option<PickerResult>> {
+    pub async fn handle_key(&mut self, key: KeyEvent) -> Result<PickerAction> {
+        self.notice = None;
+        if key.code != KeyCode::Tab {
+            self.completions = None;
+        }
         match key.code {
             KeyCode::Esc => {
-                return Ok(None); // Cancel picker
+                return Ok(PickerAction::Cancel);
+            }
+            KeyCode::Enter if self.selected_history_query().is_some() => {
+                self.input = self.selected_history_query().unwrap_or_default().to_string();
+                self.history_index = None;
+                self.rescope_to_input();
+                self.update_filter().await?;
             }
             KeyCode::Enter => {
-                return Ok(Some(self.select_current()));
+                let result = self.select_current();
+                let is_dir = result.selected_file.as_deref().is_some_and(Path::is_dir);
+                if !(is_dir && self.file_walk.is_some()) {
+                    return Ok(PickerAction::Select(result));
+                }
+                self.enter_selected_directory().await?;
             }
             KeyCode::Up => {
                 self.move_selection_up();
@@ -262,18 +940,211 @@ impl Picker {
             KeyCode::Down => {
                 self.move_selection_down();
             }
-            KeyCode::Char(c) => {
+            KeyCode::Char('*') if matches!(self.picker_type, PickerType::WordFrequency) => {
+                // Search for the selected word from the cursor, like `*`
+                let mut result = self.select_current();
+                result.search_from_start = false;
+                return Ok(PickerAction::Select(result));
+            }
+            KeyCode::Char('d')
+                if key.modifiers.contains(KeyModifiers::CONTROL) && matches!(self.picker_type, PickerType::Buffers) =>
+            {
+                if let Some(buffer_id) = self.select_current().selected_buffer_id {
+                    return Ok(PickerAction::DeleteBuffer(buffer_id));
+                }
+            }
+            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                // Toggle regex mode
+                self.regex_mode = !self.regex_mode;
+                self.update_filter().await?;
+            }
+            // Ctrl-I is Tab to a terminal, so the gitignore toggle is Ctrl-G
+            KeyCode::Char(c @ ('h' | 'g'))
+                if key.modifiers.contains(KeyModifiers::CONTROL) && self.file_walk.is_some() =>
+            {
+                self.toggle_file_filter(c == 'h');
+                self.update_filter().await?;
+            }
+            // Terminals send Ctrl-/ as Ctrl-_ or Ctrl-7
+            KeyCode::Char('/' | '_' | '7') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.show_preview = !self.show_preview;
+            }
+            KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) && !self.history.is_empty() => {
+                // Step back through the recent searches
+                let index = self.history_index.map_or(0, |i| (i + 1).min(self.history.len() - 1));
+                self.history_index = Some(index);
+                self.input = self.history[index].clone();
+                self.rescope_to_input();
+                self.update_filter().await?;
+                return Ok(PickerAction::Continue);
+            }
+            KeyCode::Char('p') | KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.move_selection_up();
+            }
+            KeyCode::Char('n') | KeyCode::Char('j') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.move_selection_down();
+            }
+            // Other control keys aren't input
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
                 self.input.push(c);
+                self.history_index = None;
+                self.update_filter().await?;
+            }
+            KeyCode::Tab if self.file_walk.is_some() => {
+                self.complete_path();
+                self.history_index = None;
+                self.rescope_to_input();
+                self.update_filter().await?;
+            }
+            KeyCode::Backspace if self.file_walk.is_some() && self.input.ends_with('/') => {
+                // Go up a directory: drop the whole last path component
+                let parent = self.input[..self.input.len() - 1].rfind('/').map_or(0, |slash| slash + 1);
+                self.input.truncate(parent);
+                self.history_index = None;
+                self.rescope_to_input();
                 self.update_filter().await?;
             }
             KeyCode::Backspace => {
                 self.input.pop();
+                self.history_index = None;
                 self.update_filter().await?;
             }
             _ => {}
         }
         
-        Ok(None)
+        Ok(PickerAction::Continue)
+    }
+    
+    /// List the newest of `history` above the items while nothing is typed,
+    /// and let `<C-P>` bring any of them back
+    pub async fn with_history(mut self, history: VecDeque<String>) -> Result<Self> {
+        self.history = history;
+        self.update_filter().await?;
+        Ok(self)
+    }
+    
+    /// Start with `query` already typed
+    pub async fn with_query(mut self, query: String) -> Result<Self> {
+        self.input = query;
+        self.update_filter().await?;
+        Ok(self)
+    }
+    
+    pub fn picker_type(&self) -> &PickerType {
+        &self.picker_type
+    }
+    
+    pub fn query(&self) -> &str {
+        &self.input
+    }
+    
+    pub fn set_notice(&mut self, notice: impl Into<String>) {
+        self.notice = Some(notice.into());
+    }
+    
+    /// Rebuild the buffer list after buffers were closed, keeping the query
+    /// and (as far as possible) the selected row
+    pub async fn refresh_buffers(&mut self, config: &Config, buffer_manager: &BufferManager) -> Result<()> {
+        let selected = self.list_state.selected();
+        self.items = Self::new_buffer_picker(config, buffer_manager).await?.items;
+        self.update_filter().await?;
+        if let Some(selected) = selected.filter(|_| !self.filtered_items.is_empty()) {
+            self.list_state.select(Some(selected.min(self.filtered_items.len() - 1)));
+        }
+        Ok(())
+    }
+    
+    // Tab: complete the input to the longest common prefix of the matching
+    // paths, then cycle through them on further presses
+    fn complete_path(&mut self) {
+        if let Some((candidates, index)) = self.completions.as_mut() {
+            let next = index.map_or(0, |i| (i + 1) % candidates.len());
+            *index = Some(next);
+            self.input = candidates[next].clone();
+            return;
+        }
+        
+        let Some(walk) = &self.file_walk else {
+            return;
+        };
+        let candidates = PathCompleter::new(walk.root.clone()).complete(&self.input);
+        match candidates.len() {
+            0 => {}
+            // A lone directory is completed; the next Tab lists what's in it
+            1 => self.input = candidates[0].clone(),
+            _ => {
+                let prefix = longest_common_prefix(&candidates);
+                if prefix.len() > self.input.len() {
+                    self.input = prefix.to_string();
+                    self.completions = Some((candidates, None));
+                } else {
+                    self.input = candidates[0].clone();
+                    self.completions = Some((candidates, Some(0)));
+                }
+            }
+        }
+    }
+    
+    // Descend into the highlighted directory entry, as Tab would complete into it
+    async fn enter_selected_directory(&mut self) -> Result<()> {
+        let selected = self.list_state.selected().and_then(|i| self.filtered_items.get(i));
+        if let Some(item) = selected.map(|&i| &self.items[i]) {
+            self.input = item.display.clone();
+            self.rescope_to_input();
+            self.update_filter().await?;
+        }
+        Ok(())
+    }
+    
+    // Flip whether the file list shows hidden files (`hidden`) or ignored
+    // ones, and walk the scope again
+    fn toggle_file_filter(&mut self, hidden: bool) {
+        let Some(walk) = self.file_walk.as_mut() else {
+            return;
+        };
+        let notice = if hidden {
+            walk.config.show_hidden = !walk.config.show_hidden;
+            if walk.config.show_hidden { "showing hidden files" } else { "hiding hidden files" }
+        } else {
+            walk.config.respect_gitignore = !walk.config.respect_gitignore;
+            if walk.config.respect_gitignore { "respecting .gitignore" } else { "ignoring .gitignore" }
+        };
+        self.items = collect_files(&walk.root.join(&walk.scope), &walk.root, &walk.config);
+        self.notice = Some(notice.to_string());
+    }
+    
+    // Limit the file list to the directory the input has been completed into
+    fn rescope_to_input(&mut self) {
+        let scope = match self.input.rfind('/') {
+            Some(slash) => &self.input[..=slash],
+            None => "",
+        };
+        let Some(walk) = self.file_walk.as_mut() else {
+            return;
+        };
+        let dir = walk.root.join(scope);
+        if walk.scope == scope || !dir.is_dir() {
+            return;
+        }
+        walk.scope = scope.to_string();
+        self.items = collect_files(&dir, &walk.root, &walk.config);
+    }
+    
+    fn selected_history_query(&self) -> Option<&str> {
+        let selected = self.list_state.selected().and_then(|i| self.filtered_items.get(i))?;
+        self.items[*selected].history_query.as_deref()
+    }
+    
+    // History entries lead the list while nothing is typed, and go away
+    // with the first key
+    fn sync_history_items(&mut self) {
+        let stale = self.items.iter().take_while(|item| item.history_query.is_some()).count();
+        self.items.drain(..stale);
+        self.filtered_items = self.filtered_items.iter().filter_map(|i| i.checked_sub(stale)).collect();
+        if self.input.is_empty() {
+            let recent: Vec<PickerItem> = self.history.iter().take(HISTORY_SHOWN).map(|query| history_item(query)).collect();
+            self.items.splice(0..0, recent);
+        }
     }
     
     fn move_selection_up(&mut self) {
@@ -304,9 +1175,17 @@ impl Picker {
         if let Some(selected_idx) = self.list_state.selected() {
             if let Some(&item_idx) = self.filtered_items.get(selected_idx) {
                 if let Some(item) = self.items.get(item_idx) {
+                    let is_word = matches!(self.picker_type, PickerType::WordFrequency);
+                    let is_line = matches!(self.picker_type, PickerType::BufferLines | PickerType::Outline);
+                    let is_spelling = matches!(self.picker_type, PickerType::Spelling);
                     return PickerResult {
                         selected_file: item.path.clone(),
                         selected_buffer_id: item.buffer_id,
+                        search_word: if is_word { item.match_text.clone() } else { None },
+                        search_from_start: is_word,
+                        // Line numbers are 1-based, rows aren't
+                        selected_line: if is_line { item.line_number.map(|n| n - 1) } else { None },
+                        replacement: if is_spelling { item.match_text.clone() } else { None },
                     };
                 }
             }
@@ -315,23 +1194,85 @@ impl Picker {
         PickerResult {
             selected_file: None,
             selected_buffer_id: None,
+            search_word: None,
+            search_from_start: false,
+            selected_line: None,
+            replacement: None,
         }
     }
     
     async fn update_filter(&mut self) -> Result<()> {
+        self.invalid_regex = false;
+        self.sync_history_items();
+        
         if self.input.is_empty() {
             // Show all items
             self.filtered_items = (0..self.items.len()).collect();
         } else {
             match &self.picker_type {
+                PickerType::Files
+                | PickerType::Buffers
+                | PickerType::Help
+                | PickerType::Messages
+                | PickerType::WordFrequency
+                | PickerType::BufferLines
+                | PickerType::Outline
+                | PickerType::Spelling
+                    if self.regex_mode =>
+                {
+                    let regex = match RegexBuilder::new(&self.input).case_insensitive(true).build() {
+                        Ok(regex) => regex,
+                        Err(_) => {
+                            // Keep the previous results until the pattern is valid again
+                            self.invalid_regex = true;
+                            return Ok(());
+                        }
+                    };
+                    self.filtered_items = self
+                        .items
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, item)| regex.is_match(&item.display))
+                        .map(|(i, _)| i)
+                        .collect();
+                    if matches!(self.picker_type, PickerType::Files | PickerType::Buffers) {
+                        self.sort_by_score(vec![0; self.filtered_items.len()]);
+                    }
+                }
                 PickerType::Files | PickerType::Buffers => {
-                    // Simple substring filtering
+                    // Fuzzy filtering, best matches first
+                    let (indices, scores): (Vec<usize>, Vec<i32>) = self
+                        .items
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(i, item)| fuzzy_score(&self.input, &item.display).map(|score| (i, score)))
+                        .unzip();
+                    self.filtered_items = indices;
+                    self.sort_by_score(scores);
+                }
+                PickerType::BufferLines | PickerType::Outline => {
+                    // Fuzzy on the line's text or the definition's name; equal scores stay in line order
+                    let (indices, scores): (Vec<usize>, Vec<i32>) = self
+                        .items
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(i, item)| {
+                            let text = item.match_text.as_deref().unwrap_or(&item.display);
+                            fuzzy_score(&self.input, text).map(|score| (i, score))
+                        })
+                        .unzip();
+                    self.filtered_items = indices;
+                    self.sort_by_score(scores);
+                }
+                PickerType::Help | PickerType::Messages | PickerType::WordFrequency | PickerType::Spelling => {
+                    // Simple substring filtering (on the word alone, not its count)
                     self.filtered_items = self
                         .items
                         .iter()
                         .enumerate()
                         .filter(|(_, item)| {
-                            item.display.to_lowercase().contains(&self.input.to_lowercase())
+                            let text = item.match_text.as_deref().unwrap_or(&item.display);
+                            text.to_lowercase().contains(&self.input.to_lowercase())
                         })
                         .map(|(i, _)| i)
                         .collect();
@@ -353,6 +1294,22 @@ impl Picker {
         Ok(())
     }
     
+    /// Order `filtered_items` by `fuzzy_score * 10 + depth_score + project_root_bonus`,
+    /// given the fuzzy score of each filtered item. Equal scores keep their order.
+    fn sort_by_score(&mut self, fuzzy_scores: Vec<i32>) {
+        let mut scored: Vec<(usize, i32)> = self
+            .filtered_items
+            .iter()
+            .zip(fuzzy_scores)
+            .map(|(&i, fuzzy)| {
+                let item = &self.items[i];
+                (i, fuzzy * 10 + item.depth_score + item.project_root_bonus)
+            })
+            .collect();
+        scored.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+        self.filtered_items = scored.into_iter().map(|(i, _)| i).collect();
+    }
+    
     async fn perform_grep_search(&mut self) -> Result<()> {
         if self.input.trim().is_empty() {
             self.items.clear();
@@ -360,48 +1317,102 @@ impl Picker {
             return Ok(());
         }
         
+        if self.regex_mode && Regex::new(&self.input).is_err() {
+            // Don't hand an invalid pattern to the search tool
+            self.invalid_regex = true;
+            return Ok(());
+        }
+        
         self.items.clear();
         
-        // Use ripgrep if available, otherwise fall back to grep
-        let output = if Command::new("rg").arg("--version").output().is_ok() {
-            Command::new("rg")
-                .args(&[
-                    "--line-number",
-                    "--no-heading",
-                    "--with-filename",
-                    &self.input,
-                ])
-                .output()
+        // Use ripgrep if available, then grep, and search in-process when
+        // neither is installed. Outside of regex mode the query is searched
+        // for literally.
+        let mut command = if Command::new("rg").arg("--version").output().is_ok() {
+            let mut args = vec!["--line-number", "--no-heading", "--with-filename"];
+            if !self.regex_mode {
+                args.push("--fixed-strings");
+            }
+            args.extend(["--regexp", self.input.as_str()]);
+            let mut command = tokio::process::Command::new("rg");
+            command.args(args);
+            command
+        } else if Command::new("grep").arg("-V").output().is_ok() {
+            let mode_flag = if self.regex_mode { "-E" } else { "-F" };
+            let mut command = tokio::process::Command::new("grep");
+            command.args(["-rn", mode_flag, "-e", &self.input, "."]);
+            command
         } else {
-            Command::new("grep")
-                .args(&["-rn", &self.input, "."])
-                .output()
+            let pattern = if self.regex_mode { self.input.clone() } else { regex::escape(&self.input) };
+            if let Ok(regex) = Regex::new(&pattern) {
+                self.items = search_files(Path::new("."), &regex, self.max_results);
+            }
+            self.filtered_items = (0..self.items.len()).collect();
+            return Ok(());
         };
         
-        if let Ok(output) = output {
-            let content = String::from_utf8_lossy(&output.stdout);
-            for line in content.lines().take(100) {
-                // Parse format: file:line:content
-                let parts: Vec<&str> = line.splitn(3, ':').collect();
-                if parts.len() >= 3 {
-                    let file = parts[0];
-                    let line_num: Option<usize> = parts[1].parse().ok();
-                    let content = parts[2];
-                    
-                    let display = format!("{}:{}: {}", file, parts[1], content);
-                    
-                    self.items.push(PickerItem {
-                        display,
-                        path: Some(PathBuf::from(file)),
-                        buffer_id: None,
-                        line_number: line_num,
-                        match_text: Some(content.to_string()),
-                    });
+        // Read matches as they arrive so a broad pattern never has its whole
+        // output in memory, and stop the search once there are enough
+        let child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn();
+        if let Ok(mut child) = child {
+            if let Some(stdout) = child.stdout.take() {
+                let mut reader = BufReader::new(stdout);
+                let mut line = Vec::new();
+                while self.items.len() < self.max_results
+                    && reader.read_until(b'\n', &mut line).await.is_ok_and(|read| read > 0)
+                {
+                    let text = String::from_utf8_lossy(&line);
+                    if let Some((file, line_num, content)) = parse_grep_line(text.trim_end_matches(['\n', '\r'])) {
+                        self.items.push(grep_item(file, line_num, content));
+                    }
+                    line.clear();
                 }
             }
+            // Stops a search that still has matches to give; reaps a finished one
+            let _ = child.kill().await;
         }
         
         self.filtered_items = (0..self.items.len()).collect();
         Ok(())
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grep_line_keeps_windows_drive_in_file_name() {
+        assert_eq!(
+            parse_grep_line(r"C:\src\main.rs:10:fn main() {"),
+            Some((r"C:\src\main.rs", 10, "fn main() {"))
+        );
+        assert_eq!(parse_grep_line("C:/src/lib.rs:3:a:b"), Some(("C:/src/lib.rs", 3, "a:b")));
+    }
+
+    #[test]
+    fn grep_line_without_drive() {
+        assert_eq!(parse_grep_line("src/main.rs:7:let x = 1;"), Some(("src/main.rs", 7, "let x = 1;")));
+        assert_eq!(parse_grep_line("src/main.rs:x:text"), None);
+        assert_eq!(parse_grep_line("no separators"), None);
+    }
+
+    #[tokio::test]
+    async fn ctrl_p_steps_back_through_history_until_the_query_is_edited() {
+        let history = VecDeque::from(["newest".to_string(), "older".to_string(), "oldest".to_string()]);
+        let mut picker = Picker::new_messages_picker(&[]).with_history(history).await.unwrap();
+        let ctrl_p = KeyEvent::new(KeyCode::Char('p'), KeyModifiers::CONTROL);
+
+        picker.handle_key(ctrl_p).await.unwrap();
+        picker.handle_key(ctrl_p).await.unwrap();
+        assert_eq!(picker.query(), "older");
+
+        picker.handle_key(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE)).await.unwrap();
+        picker.handle_key(ctrl_p).await.unwrap();
+        assert_eq!(picker.query(), "newest");
+    }
+}
@@ -0,0 +1,105 @@
+use anyhow::Result;
+use std::io::Write;
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc::{self, error::TryRecvError, UnboundedReceiver};
+
+/// What a `:terminal` command has printed since it was last polled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TerminalOutput {
+    Line(String),
+    /// The exit code, `None` if a signal ended it
+    Exited(Option<i32>),
+}
+
+/// A shell command started with `:terminal {cmd}`, its output read into a
+/// buffer of its own as it comes. There's no pseudo-terminal yet, so the
+/// command can't be typed into and sees no terminal on its stdin.
+pub struct TerminalJob {
+    pub buffer_id: usize,
+    output: UnboundedReceiver<TerminalOutput>,
+}
+
+impl TerminalJob {
+    /// Run `command` with `sh`, stderr going the same way as stdout. The
+    /// command is killed once the job is dropped.
+    pub fn spawn(command: &str, buffer_id: usize) -> Result<Self> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(format!("exec 2>&1\n{}", command))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdout = child.stdout.take();
+        let (sender, output) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            if let Some(stdout) = stdout {
+                // Output that isn't UTF-8 still comes through, replaced in part
+                let mut lines = BufReader::new(stdout).split(b'\n');
+                while let Ok(Some(line)) = lines.next_segment().await {
+                    if sender.send(TerminalOutput::Line(plain(&String::from_utf8_lossy(&line)))).is_err() {
+                        return;
+                    }
+                }
+            }
+            let code = child.wait().await.ok().and_then(|status| status.code());
+            let _ = sender.send(TerminalOutput::Exited(code));
+        });
+        Ok(Self { buffer_id, output })
+    }
+
+    /// The output that came since the last call, and whether the command is
+    /// done
+    pub fn poll(&mut self) -> (Vec<TerminalOutput>, bool) {
+        let mut output = Vec::new();
+        loop {
+            match self.output.try_recv() {
+                Ok(next) => output.push(next),
+                Err(TryRecvError::Empty) => return (output, false),
+                Err(TryRecvError::Disconnected) => return (output, true),
+            }
+        }
+    }
+}
+
+/// Run `command` with `sh` on `input`, as `:{range}!` does, returning what
+/// it printed on stdout and stderr and its exit code if it failed
+pub fn filter(command: &str, input: &str) -> Result<(String, Option<i32>)> {
+    let mut child = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("exec 2>&1\n{}", command))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    // Written from another thread so a command printing as it reads can't
+    // fill its output pipe while waiting on the rest of its input
+    let input = input.to_string();
+    let stdin = child.stdin.take();
+    let writer = std::thread::spawn(move || stdin.map(|mut stdin| stdin.write_all(input.as_bytes())));
+    let output = child.wait_with_output()?;
+    // Commands that don't read all of their input, like `head`, are fine
+    let _ = writer.join();
+    let failed = (!output.status.success()).then(|| output.status.code()).flatten();
+    Ok((String::from_utf8_lossy(&output.stdout).into_owned(), failed))
+}
+
+// `line` without the escape sequences and other control characters a
+// terminal would have acted on, which the editor can't show
+fn plain(line: &str) -> String {
+    let mut text = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            // `ESC [` starts a sequence that runs up to a final letter-like byte
+            '\x1b' if chars.next_if_eq(&'[').is_some() => {
+                while chars.next().is_some_and(|c| !('@'..='~').contains(&c)) {}
+            }
+            '\t' => text.push(c),
+            c if c.is_control() => {}
+            c => text.push(c),
+        }
+    }
+    text
+}
@@ -7,6 +7,8 @@ use ratatui::{
     Frame,
 };
 
+use std::path::{Path, PathBuf};
+
 use crate::config::Config;
 
 const ZEN_VIM_ART: &[&str] = &[
@@ -31,26 +33,59 @@ const MENU_ITEMS: &[(&str, &str, &str)] = &[
     ("q", "Quit", "quit"),
 ];
 
+/// The project the editor was started in, shown under the header
+#[derive(Debug, Clone)]
+pub struct ProjectInfo {
+    pub name: String,
+    pub branch: Option<String>,
+}
+
 pub struct Dashboard {
     config: Config,
+    project: Option<ProjectInfo>,
+    // Opened with the keys 1 to 9
+    recent_files: Vec<PathBuf>,
+    pending_keys: String,
 }
 
 impl Dashboard {
-    pub fn new(config: &Config) -> Self {
+    pub fn new(config: &Config, project: Option<ProjectInfo>, recent_files: Vec<PathBuf>) -> Self {
         Self {
             config: config.clone(),
+            project,
+            recent_files,
+            pending_keys: String::new(),
+        }
+    }
+    
+    // The recent files shown, which `dashboard.show_recent_files` can turn off
+    fn shown_recent_files(&self) -> &[PathBuf] {
+        if !self.config.dashboard.show_recent_files {
+            return &[];
         }
+        let shown = self.recent_files.len().min(self.config.dashboard.max_recent_files).min(9);
+        &self.recent_files[..shown]
+    }
+    
+    /// True while a multi-key menu sequence has been started but not completed
+    pub fn has_pending_keys(&self) -> bool {
+        !self.pending_keys.is_empty()
     }
     
     pub fn render(&self, frame: &mut Frame, area: Rect) {
+        let recent_files = self.shown_recent_files();
+        // A heading and a blank line around the entries
+        let recent_height = if recent_files.is_empty() { 0 } else { recent_files.len() as u16 + 2 };
+        
         // Create vertical layout
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
                 Constraint::Length(3),           // Top padding
                 Constraint::Length(ZEN_VIM_ART.len() as u16), // ASCII art
-                Constraint::Length(2),           // Separator
+                Constraint::Length(2),           // Project name
                 Constraint::Length(MENU_ITEMS.len() as u16), // Menu items
+                Constraint::Length(recent_height), // Recent files
                 Constraint::Length(3),           // Instructions
                 Constraint::Min(1),              // Bottom padding
             ])
@@ -73,6 +108,22 @@ impl Dashboard {
         
         frame.render_widget(art_paragraph, chunks[1]);
         
+        // Render project name and branch
+        if let Some(ref project) = self.project {
+            let mut spans = vec![
+                Span::styled("Project: ", Style::default().fg(Color::DarkGray)),
+                Span::styled(project.name.as_str(), Style::default().fg(Color::Yellow)),
+            ];
+            if let Some(ref branch) = project.branch {
+                spans.push(Span::styled(format!("  ({})", branch), Style::default().fg(Color::Green)));
+            }
+            
+            let project_paragraph = Paragraph::new(Line::from(spans))
+                .alignment(Alignment::Center);
+            
+            frame.render_widget(project_paragraph, chunks[2]);
+        }
+        
         // Render menu items
         let menu_lines: Vec<Line> = MENU_ITEMS
             .iter()
@@ -91,36 +142,91 @@ impl Dashboard {
         
         frame.render_widget(menu_paragraph, chunks[3]);
         
+        // Render recent files
+        if !recent_files.is_empty() {
+            let mut recent_lines = vec![
+                Line::raw(""),
+                Line::from(Span::styled("    Recent Files", Style::default().fg(Color::DarkGray))),
+            ];
+            recent_lines.extend(recent_files.iter().enumerate().map(|(i, path)| {
+                Line::from(vec![
+                    Span::raw("    ["),
+                    Span::styled((i + 1).to_string(), Style::default().fg(Color::Yellow)),
+                    Span::raw("]  "),
+                    Span::styled(display_path(path), Style::default().fg(Color::White)),
+                ])
+            }));
+            frame.render_widget(Paragraph::new(recent_lines), chunks[4]);
+        }
+        
         // Render instructions
         let instructions = vec![
             Line::from(vec![
                 Span::raw("    Leader key: "),
                 Span::styled("<space>", Style::default().fg(Color::Green)),
             ]),
-            Line::from(Span::styled(
-                "    Press any menu key to continue...",
-                Style::default().fg(Color::DarkGray),
-            )),
+            if self.pending_keys.is_empty() {
+                Line::from(Span::styled(
+                    "    Press any menu key to continue...",
+                    Style::default().fg(Color::DarkGray),
+                ))
+            } else {
+                Line::from(vec![
+                    Span::styled("    Pending: ", Style::default().fg(Color::DarkGray)),
+                    Span::styled(self.pending_keys.as_str(), Style::default().fg(Color::Yellow)),
+                ])
+            },
         ];
         
         let instructions_paragraph = Paragraph::new(instructions)
             .block(Block::default().borders(Borders::NONE));
         
-        frame.render_widget(instructions_paragraph, chunks[4]);
+        frame.render_widget(instructions_paragraph, chunks[5]);
     }
     
     pub fn handle_key(&mut self, key: KeyEvent) -> Option<String> {
         match key.code {
+            KeyCode::Char(c @ '1'..='9') if self.pending_keys.is_empty() => {
+                let index = c as usize - '1' as usize;
+                let path = self.shown_recent_files().get(index)?;
+                Some(format!("open {}", path.display()))
+            }
             KeyCode::Char(c) => {
-                // Check if it matches any menu item
-                for (menu_key, _, action) in MENU_ITEMS {
-                    if menu_key.chars().any(|ch| ch == c) {
-                        return Some(action.to_string());
-                    }
+                self.pending_keys.push(c);
+                
+                // A full match fires immediately
+                if let Some((_, _, action)) = MENU_ITEMS
+                    .iter()
+                    .find(|(menu_key, _, _)| *menu_key == self.pending_keys)
+                {
+                    self.pending_keys.clear();
+                    return Some(action.to_string());
+                }
+                
+                // Keep buffering while the keys are a prefix of some menu key
+                if !MENU_ITEMS
+                    .iter()
+                    .any(|(menu_key, _, _)| menu_key.starts_with(self.pending_keys.as_str()))
+                {
+                    self.pending_keys.clear();
                 }
                 None
             }
-            _ => None,
+            _ => {
+                self.pending_keys.clear();
+                None
+            }
         }
     }
-} 
\ No newline at end of file
+}
+
+// `path` relative to the working directory, or with the home directory as `~`
+fn display_path(path: &Path) -> String {
+    if let Some(relative) = std::env::current_dir().ok().and_then(|cwd| path.strip_prefix(cwd).ok().map(Path::to_path_buf)) {
+        return relative.display().to_string();
+    }
+    match std::env::var("HOME").ok().and_then(|home| path.strip_prefix(home).ok().map(Path::to_path_buf)) {
+        Some(relative) => format!("~/{}", relative.display()),
+        None => path.display().to_string(),
+    }
+}
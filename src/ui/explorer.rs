@@ -0,0 +1,218 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Directories never worth browsing
+const SKIPPED_DIRECTORIES: &[&str] = &[".git"];
+
+/// Columns the sidebar takes on the left, border included, until resized
+/// with `Ctrl-W <` and `>`; it never gets narrower than `MIN_WIDTH`
+pub const DEFAULT_WIDTH: u16 = 30;
+pub const MIN_WIDTH: u16 = 12;
+
+/// A visible row of the tree
+#[derive(Debug, Clone)]
+pub struct ExplorerEntry {
+    pub path: PathBuf,
+    pub depth: usize,
+    pub is_dir: bool,
+}
+
+/// What the app should do after the explorer handled a key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExplorerAction {
+    Continue,
+    /// Give the keys back to the editor, leaving the sidebar open
+    Unfocus,
+    Close,
+    /// Open the file in the main editor
+    Open(PathBuf),
+}
+
+/// A file tree sidebar for `<leader>e`
+pub struct Explorer {
+    root: PathBuf,
+    expanded: HashSet<PathBuf>,
+    entries: Vec<ExplorerEntry>,
+    selected: usize,
+    /// Keys go to the explorer rather than the editor
+    pub focused: bool,
+    pub width: u16,
+}
+
+impl Explorer {
+    pub fn new(root: PathBuf) -> Self {
+        let mut explorer = Self {
+            root,
+            expanded: HashSet::new(),
+            entries: Vec::new(),
+            selected: 0,
+            focused: true,
+            width: DEFAULT_WIDTH,
+        };
+        explorer.refresh();
+        explorer
+    }
+
+    /// The columns the explorer takes in a window `width` wide, `None` while
+    /// that is too narrow to show it next to the editor
+    pub fn shown_width(&self, width: u16) -> Option<u16> {
+        (width > DEFAULT_WIDTH * 2).then(|| self.width.min(width / 2))
+    }
+    
+    /// Re-read the expanded directories, keeping the selected path selected
+    pub fn refresh(&mut self) {
+        let selected = self.selected_entry().map(|entry| entry.path.clone());
+        self.entries.clear();
+        let root = self.root.clone();
+        self.push_children(&root, 0);
+
+        self.selected = selected
+            .and_then(|path| self.entries.iter().position(|entry| entry.path == path))
+            .unwrap_or(0)
+            .min(self.entries.len().saturating_sub(1));
+    }
+
+    // Directories first, then files, each sorted by name
+    fn push_children(&mut self, dir: &Path, depth: usize) {
+        let Ok(read_dir) = fs::read_dir(dir) else {
+            return;
+        };
+        let mut children: Vec<(PathBuf, bool)> = read_dir
+            .filter_map(|entry| entry.ok())
+            .map(|entry| {
+                let path = entry.path();
+                let is_dir = path.is_dir();
+                (path, is_dir)
+            })
+            .filter(|(path, is_dir)| {
+                !(*is_dir && path.file_name().is_some_and(|name| SKIPPED_DIRECTORIES.iter().any(|skipped| name == *skipped)))
+            })
+            .collect();
+        children.sort_by(|(a, a_dir), (b, b_dir)| b_dir.cmp(a_dir).then_with(|| a.file_name().cmp(&b.file_name())));
+
+        for (path, is_dir) in children {
+            let expanded = is_dir && self.expanded.contains(&path);
+            self.entries.push(ExplorerEntry { path: path.clone(), depth, is_dir });
+            if expanded {
+                self.push_children(&path, depth + 1);
+            }
+        }
+    }
+
+    pub fn selected_entry(&self) -> Option<&ExplorerEntry> {
+        self.entries.get(self.selected)
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> ExplorerAction {
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.selected = (self.selected + 1).min(self.entries.len().saturating_sub(1));
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            KeyCode::Char('g') | KeyCode::Home => self.selected = 0,
+            KeyCode::Char('G') | KeyCode::End => self.selected = self.entries.len().saturating_sub(1),
+            KeyCode::Enter | KeyCode::Char('l') | KeyCode::Char('o') | KeyCode::Right => {
+                let Some(entry) = self.selected_entry().cloned() else {
+                    return ExplorerAction::Continue;
+                };
+                if !entry.is_dir {
+                    return ExplorerAction::Open(entry.path);
+                }
+                // Enter toggles a directory, `l` only ever opens one
+                if !self.expanded.insert(entry.path.clone()) && key.code == KeyCode::Enter {
+                    self.expanded.remove(&entry.path);
+                }
+                self.refresh();
+            }
+            KeyCode::Char('h') | KeyCode::Left => self.collapse(),
+            KeyCode::Char('R') => self.refresh(),
+            KeyCode::Esc => return ExplorerAction::Unfocus,
+            KeyCode::Char('q') => return ExplorerAction::Close,
+            _ => {}
+        }
+        ExplorerAction::Continue
+    }
+
+    // Collapse the selected directory, or else move to its parent
+    fn collapse(&mut self) {
+        let Some(entry) = self.selected_entry().cloned() else {
+            return;
+        };
+        if entry.is_dir && self.expanded.remove(&entry.path) {
+            self.refresh();
+            return;
+        }
+        let parent = entry.path.parent().map(Path::to_path_buf);
+        if let Some(row) = parent.and_then(|parent| self.entries.iter().position(|entry| entry.path == parent)) {
+            self.selected = row;
+        }
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect) {
+        let title = self
+            .root
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.root.display().to_string());
+        let block = Block::default()
+            .borders(Borders::RIGHT)
+            .title(Span::styled(title, Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)))
+            .border_style(Style::default().fg(Color::DarkGray));
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        if self.entries.is_empty() {
+            let empty = Paragraph::new(Span::styled("(empty)", Style::default().fg(Color::DarkGray)));
+            frame.render_widget(empty, inner);
+            return;
+        }
+
+        // Scroll just enough to keep the selection on screen
+        let height = inner.height as usize;
+        let offset = (self.selected + 1).saturating_sub(height);
+        let lines: Vec<Line> = self
+            .entries
+            .iter()
+            .enumerate()
+            .skip(offset)
+            .take(height)
+            .map(|(row, entry)| {
+                let name = entry.path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+                let (marker, style) = if entry.is_dir {
+                    let marker = if self.expanded.contains(&entry.path) { "▾ " } else { "▸ " };
+                    (marker, Style::default().fg(Color::Blue))
+                } else {
+                    ("  ", Style::default().fg(Color::White))
+                };
+                let style = if row == self.selected {
+                    let bg = if self.focused { Color::DarkGray } else { Color::Black };
+                    style.bg(bg).add_modifier(Modifier::BOLD)
+                } else {
+                    style
+                };
+                let suffix = if entry.is_dir { "/" } else { "" };
+                Line::from(Span::styled(
+                    format!("{}{}{}{}", "  ".repeat(entry.depth), marker, name, suffix),
+                    style,
+                ))
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines), inner);
+
+        if self.focused {
+            let y = inner.y + (self.selected - offset) as u16;
+            frame.set_cursor(inner.x, y);
+        }
+    }
+}
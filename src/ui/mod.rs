@@ -1,29 +1,91 @@
 pub mod dashboard;
+pub mod explorer;
 
-use anyhow::Result;
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph},
+    widgets::{Block, Borders, Clear, Paragraph},
     Frame,
 };
 
+use std::path::Path;
+use std::time::Duration;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
 use crate::config::Config;
-use crate::core::BufferManager;
+use crate::core::buffer::{DetectedIndent, Diagnostic, InlayHint, InlayKind, Severity};
+use crate::core::spell::SpellChecker;
+use crate::core::syntax::{Highlight, SyntaxKind};
+use crate::core::wrap;
+use crate::core::{BufferManager, Position};
+use crate::modes::completion::Completer;
+use crate::modes::ex::SubstitutePreview;
 use crate::modes::{Mode, ModeManager};
 
 pub use dashboard::Dashboard;
+pub use explorer::Explorer;
+
+// Zen mode: a fixed-width writing column on a soft light page
+const ZEN_COLUMN_WIDTH: u16 = 80;
+const ZEN_VERTICAL_PADDING: u16 = 2;
+const ZEN_PAGE: Color = Color::Rgb(250, 246, 238);
+const ZEN_TEXT: Color = Color::Rgb(70, 66, 60);
+const ZEN_CURSOR_LINE: Color = Color::Rgb(238, 231, 218);
+
+// The bracket under the cursor and its match, like Vim's matchparen
+const MATCH_PAREN: Style = Style::new().fg(Color::Black).bg(Color::Cyan);
+
+// Words missing from the spell checker's list
+const SPELL_BAD: Style = Style::new().fg(Color::LightRed).add_modifier(Modifier::UNDERLINED);
+
+// Whitespace made visible by `list`
+const LIST_CHARS: Style = Style::new().fg(Color::DarkGray);
+
+// What the `:s` being typed would replace
+const SUBSTITUTE_MATCH: Style = Style::new().fg(Color::Black).bg(Color::Yellow);
+
+// The line a closed fold is shown as
+const FOLDED: Style = Style::new().fg(Color::Cyan);
+
+// Insert mode completions, and the one in the text
+const COMPLETION_ITEM: Style = Style::new().fg(Color::Black).bg(Color::Gray);
+const COMPLETION_SELECTED: Style = Style::new().fg(Color::White).bg(Color::Blue);
+
+// Completions listed at once; the list scrolls to keep the selected one in view
+const COMPLETION_ROWS: usize = 8;
 
 pub struct UI {
     config: Config,
+    // Loaded once `ui.spell` is first on
+    spell: Option<SpellChecker>,
 }
 
 impl UI {
     pub fn new(config: &Config) -> Self {
-        Self {
+        let mut ui = Self {
             config: config.clone(),
+            spell: None,
+        };
+        ui.set_config(config);
+        ui
+    }
+    
+    /// Use a changed config, keeping the word list unless `spell_file` changed
+    pub fn set_config(&mut self, config: &Config) {
+        if config.ui.spell_file != self.config.ui.spell_file {
+            self.spell = None;
+        }
+        if config.ui.spell && self.spell.is_none() {
+            self.spell = SpellChecker::load(Path::new(&config.ui.spell_file)).ok();
         }
+        self.config = config.clone();
+    }
+    
+    /// The loaded word list, while `spell` is on
+    pub fn spell_checker(&self) -> Option<&SpellChecker> {
+        self.spell.as_ref().filter(|_| self.config.ui.spell)
     }
     
     pub fn render(
@@ -32,7 +94,24 @@ impl UI {
         buffer_manager: &BufferManager,
         mode_manager: &ModeManager,
         area: Rect,
+        zen_mode: bool,
+        explorer: Option<&Explorer>,
     ) {
+        if zen_mode {
+            self.render_zen(frame, buffer_manager, mode_manager, area);
+            return;
+        }
+        
+        // The explorer takes a column on the left, the editor gets the rest
+        // and at least half
+        let explorer_width = explorer.and_then(|explorer| explorer.shown_width(area.width));
+        let explorer = explorer.filter(|_| explorer_width.is_some());
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Length(explorer_width.unwrap_or(0)), Constraint::Min(1)])
+            .split(area);
+        let area = columns[1];
+        
         // Create layout
         let chunks = Layout::default()
             .direction(Direction::Vertical)
@@ -43,66 +122,300 @@ impl UI {
             .split(area);
         
         // Render editor
-        self.render_editor(frame, buffer_manager, chunks[0]);
+        let selection = mode_manager.visual_selection(buffer_manager);
+        let preview = mode_manager.substitute_preview(buffer_manager);
+        let cursor = self.render_editor(frame, buffer_manager, chunks[0], false, selection, preview.as_ref());
+        if let (Some(completer), Some(cursor)) = (mode_manager.completion(), cursor) {
+            self.render_completion(frame, completer, cursor, chunks[0]);
+        }
         
         // Render status line or command line
         if mode_manager.current_mode() == Mode::Command {
             self.render_command_line(frame, mode_manager, chunks[1]);
+        } else if let Some(message) = mode_manager.message() {
+            let message_line = Paragraph::new(message)
+                .style(Style::default().fg(Color::White).bg(Color::DarkGray));
+            frame.render_widget(message_line, chunks[1]);
         } else {
             // Always show status line to display current file info
             self.render_status_line(frame, buffer_manager, mode_manager, chunks[1]);
         }
+        
+        // Last, so a focused explorer gets the cursor
+        if let Some(explorer) = explorer {
+            explorer.render(frame, columns[0]);
+        }
     }
     
-    fn render_editor(&self, frame: &mut Frame, buffer_manager: &BufferManager, area: Rect) {
+    fn render_zen(
+        &self,
+        frame: &mut Frame,
+        buffer_manager: &BufferManager,
+        mode_manager: &ModeManager,
+        area: Rect,
+    ) {
+        frame.render_widget(Block::default().style(Style::default().bg(ZEN_PAGE)), area);
+        
+        // No status line; the bottom row only appears while it has something to say
+        let show_bottom = mode_manager.current_mode() == Mode::Command || mode_manager.message().is_some();
+        let mut page = area;
+        if show_bottom {
+            page.height = page.height.saturating_sub(1);
+        }
+        
+        let selection = mode_manager.visual_selection(buffer_manager);
+        let preview = mode_manager.substitute_preview(buffer_manager);
+        let cursor = self.render_editor(frame, buffer_manager, zen_column(page), true, selection, preview.as_ref());
+        if let (Some(completer), Some(cursor)) = (mode_manager.completion(), cursor) {
+            self.render_completion(frame, completer, cursor, page);
+        }
+        
+        if show_bottom {
+            let bottom = Rect { y: area.y + page.height, height: 1, ..area };
+            if mode_manager.current_mode() == Mode::Command {
+                self.render_command_line(frame, mode_manager, bottom);
+            } else if let Some(message) = mode_manager.message() {
+                let message_line = Paragraph::new(message)
+                    .style(Style::default().fg(ZEN_TEXT).bg(ZEN_PAGE));
+                frame.render_widget(message_line, bottom);
+            }
+        }
+    }
+    
+    /// Draw the current buffer. Returns where the cursor went, if it is on screen.
+    fn render_editor(
+        &self,
+        frame: &mut Frame,
+        buffer_manager: &BufferManager,
+        area: Rect,
+        zen_mode: bool,
+        selection: Option<(Position, Position)>,
+        preview: Option<&SubstitutePreview>,
+    ) -> Option<(u16, u16)> {
+        let mut cursor = None;
+        let show_line_numbers = self.config.ui.show_line_numbers && !zen_mode;
+        let show_inlay_hints = self.config.ui.inlay_hints && !zen_mode;
+        let spell = self.spell_checker();
+        let diagnostics: &[Diagnostic] = match buffer_manager.current_buffer() {
+            Some(buffer) if !zen_mode => &buffer.diagnostics,
+            _ => &[],
+        };
+        // Signs for the diagnostics go left of the line numbers, while there are any
+        let signs = if diagnostics.is_empty() { 0 } else { 2 };
+        let gutter = signs + if show_line_numbers { 5 } else { 0 };
+        // Soft wrapping splits lines into rows this many cells wide
+        let wrap_width = Some((area.width as usize).saturating_sub(gutter))
+            .filter(|&width| self.config.ui.wrap_lines && width > 0);
+        
         if let Some(buffer) = buffer_manager.current_buffer() {
             let cursor_pos = buffer.cursor.position();
+            // A closed fold shows as its first line, wherever in it the cursor is
+            let cursor_row = buffer.closed_fold_at(cursor_pos.row).map_or(cursor_pos.row, |fold| fold.start);
+            buffer.hints_shown.set(show_inlay_hints);
+            // Screen rows a buffer row takes: wrapped lines take several, and
+            // the rows inside a closed fold none
+            let rows = |row: usize| match (buffer.closed_fold_at(row), wrap_width) {
+                (Some(fold), _) => usize::from(fold.start == row),
+                (None, Some(width)) => {
+                    let line = &buffer.content[row];
+                    wrap::rows(line, buffer.shown_hints(row), width, wrap::break_indent(line, width, &self.config.ui))
+                }
+                (None, None) => 1,
+            };
+            // The first row shown after skipping a closed fold at `row`
+            let next_row = |row: usize| buffer.closed_fold_at(row).map_or(row, |fold| fold.end) + 1;
+            // The highest row the screen can start at with `height` rows
+            // down to the cursor line in view
+            let top_for = |height: usize| {
+                let mut top = cursor_row;
+                let mut used = rows(cursor_row);
+                while top > 0 {
+                    let above = buffer.closed_fold_at(top - 1).map_or(top - 1, |fold| fold.start);
+                    used += rows(above);
+                    if used > height {
+                        break;
+                    }
+                    top = above;
+                }
+                top
+            };
             
-            // Calculate viewport
+            // Calculate viewport, scrolling only as far as the cursor needs
             let visible_lines = area.height as usize;
-            let start_line = if cursor_pos.row >= visible_lines {
-                cursor_pos.row - visible_lines + 1
+            let mut start_line = buffer.top_line.get().min(buffer.content.len().saturating_sub(1));
+            start_line = buffer.closed_fold_at(start_line).map_or(start_line, |fold| fold.start);
+            if buffer.center_cursor.take() {
+                start_line = top_for(visible_lines / 2 + 1);
+            }
+            if cursor_row < start_line {
+                start_line = cursor_row;
+            } else {
+                start_line = start_line.max(top_for(visible_lines));
+            }
+            buffer.top_line.set(start_line);
+            buffer.wrap_width.set(wrap_width);
+            
+            // Cells left of the cursor in its line, inlay hints included
+            let cursor_cells = match buffer.closed_fold_at(cursor_pos.row) {
+                Some(_) => 0,
+                None => {
+                    wrap::cells(&buffer.content[cursor_pos.row], buffer.shown_hints(cursor_pos.row), cursor_pos.col)
+                }
+            };
+            // Unwrapped lines are cut off at the edge, scrolling sideways as
+            // far as the cursor needs
+            let left_col = match wrap_width {
+                Some(_) => 0,
+                None => {
+                    let text_width = (area.width as usize).saturating_sub(gutter).max(1);
+                    buffer.left_col.get().min(cursor_cells).max((cursor_cells + 1).saturating_sub(text_width))
+                }
+            };
+            buffer.left_col.set(left_col);
+            let mut end_line = start_line;
+            let mut shown_rows = 0;
+            while end_line < buffer.content.len() && shown_rows < visible_lines {
+                shown_rows += rows(end_line);
+                end_line = next_row(end_line).min(buffer.content.len());
+            }
+            
+            // Everything from here on works on rows `start_line..end_line`
+            // only, so long files draw as fast as short ones
+            let diagnostics: Vec<&Diagnostic> = diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.end.row >= start_line && diagnostic.start.row < end_line)
+                .collect();
+            
+            // Zen mode stays monochrome
+            let highlights = if zen_mode { None } else { buffer.line_highlights(start_line, end_line) };
+            
+            // The bracket under the cursor and its match, if that is on screen
+            let brackets: Vec<Position> = if zen_mode || buffer.large_file_mode {
+                Vec::new()
             } else {
-                0
+                buffer
+                    .find_matching_bracket(cursor_pos, visible_lines)
+                    .filter(|found| (start_line..end_line).contains(&found.row))
+                    .map(|found| vec![cursor_pos, found])
+                    .unwrap_or_default()
             };
-            let end_line = (start_line + visible_lines).min(buffer.content.len());
             
             // Prepare content
             let mut lines = Vec::new();
-            for (i, line) in buffer.content[start_line..end_line].iter().enumerate() {
-                let line_number = start_line + i;
-                let is_cursor_line = line_number == cursor_pos.row;
+            // Screen row the cursor line starts on
+            let mut cursor_line_row = 0;
+            let mut line_number = start_line;
+            while line_number < end_line {
+                let line = &buffer.content[line_number];
+                let i = line_number - start_line;
+                let is_cursor_line = line_number == cursor_row;
+                if is_cursor_line {
+                    cursor_line_row = lines.len();
+                }
                 
-                // Add line numbers if enabled
-                let content = if self.config.ui.show_line_numbers {
-                    format!("{:4} {}", line_number + 1, line)
-                } else {
-                    line.clone()
+                let style = match (zen_mode, is_cursor_line) {
+                    (true, true) => Style::default().fg(ZEN_TEXT).bg(ZEN_CURSOR_LINE),
+                    (true, false) => Style::default().fg(ZEN_TEXT).bg(ZEN_PAGE),
+                    (false, true) => Style::default().bg(Color::DarkGray),
+                    (false, false) => Style::default(),
                 };
                 
-                let style = if is_cursor_line {
-                    Style::default().bg(Color::DarkGray)
+                let mut spans = Vec::new();
+                
+                if signs > 0 {
+                    spans.push(diagnostic_sign(&diagnostics, line_number, style));
+                }
+                // Add line numbers if enabled
+                if show_line_numbers {
+                    spans.push(Span::styled(format!("{:4} ", line_number + 1), style));
+                }
+                
+                if let Some(fold) = buffer.closed_fold_at(line_number) {
+                    // Like Vim's foldtext: a dash per level, the size and the first line
+                    let level = buffer.folds.iter().filter(|other| other.contains(fold.start)).count();
+                    let lines_word = if fold.lines() == 1 { "line" } else { "lines" };
+                    let text = format!("+-{}{:>3} {}: {}", "-".repeat(level), fold.lines(), lines_word, line.trim_start());
+                    let width = (area.width as usize).saturating_sub(gutter);
+                    let text: String = text.chars().take(width).collect();
+                    let fold_style = if zen_mode { style } else { style.patch(FOLDED) };
+                    spans.push(Span::styled(format!("{:-<width$}", text, width = width), fold_style));
+                    lines.push(Line::from(spans));
+                    line_number = fold.end + 1;
+                    continue;
+                }
+                
+                let mut text_spans = match highlights.as_ref().and_then(|highlights| highlights.get(i)) {
+                    Some(line_highlights) => highlight_spans(line, line_highlights, style),
+                    None => vec![Span::styled(line.clone(), style)],
+                };
+                if let Some(range) = selection.and_then(|selection| selected_bytes(line, line_number, selection)) {
+                    text_spans = restyle_spans(text_spans, range, Style::default().bg(Color::Blue));
+                }
+                for range in spell.map(|spell| spell.misspelled(line)).unwrap_or_default() {
+                    text_spans = restyle_spans(text_spans, range, SPELL_BAD);
+                }
+                for diagnostic in diagnostics.iter().filter(|d| (d.start.row..=d.end.row).contains(&line_number)) {
+                    let patch = Style::new().underline_color(severity_color(diagnostic.severity)).add_modifier(Modifier::UNDERLINED);
+                    text_spans = restyle_spans(text_spans, diagnostic_bytes(line, line_number, diagnostic), patch);
+                }
+                for range in preview.map(|preview| preview.matches(line_number, line)).unwrap_or_default() {
+                    text_spans = restyle_spans(text_spans, range, SUBSTITUTE_MATCH);
+                }
+                for bracket in brackets.iter().filter(|bracket| bracket.row == line_number) {
+                    let from = byte_index(line, bracket.col);
+                    text_spans = restyle_spans(text_spans, (from, byte_index(line, bracket.col + 1)), MATCH_PAREN);
+                }
+                // Hints go where the shown characters are, which `list` may change
+                let shown_line = if self.config.ui.list {
+                    text_spans = show_whitespace(text_spans, line);
+                    std::borrow::Cow::Owned(text_spans.iter().map(|span| span.content.as_ref()).collect::<String>())
                 } else {
-                    Style::default()
+                    std::borrow::Cow::Borrowed(line)
                 };
+                if let Some(hints) = buffer.inlay_hints.get(&line_number).filter(|_| show_inlay_hints) {
+                    text_spans = insert_hints(text_spans, &shown_line, hints, style);
+                }
                 
-                lines.push(Line::from(Span::styled(content, style)));
+                match wrap_width {
+                    Some(width) => {
+                        let indent = wrap::break_indent(line, width, &self.config.ui);
+                        for (row, mut row_spans) in wrap_spans(text_spans, width, indent, style).into_iter().enumerate() {
+                            // Continuation rows leave the line number column blank
+                            let mut row_line = if row == 0 {
+                                std::mem::take(&mut spans)
+                            } else if gutter > 0 {
+                                vec![Span::styled(" ".repeat(gutter), style)]
+                            } else {
+                                Vec::new()
+                            };
+                            row_line.append(&mut row_spans);
+                            lines.push(Line::from(row_line));
+                        }
+                    }
+                    None => {
+                        spans.extend(scroll_spans(text_spans, left_col));
+                        lines.push(Line::from(spans));
+                    }
+                }
+                line_number += 1;
             }
             
-            let paragraph = Paragraph::new(lines)
-                .block(Block::default().borders(Borders::NONE))
-                .wrap(ratatui::widgets::Wrap { trim: !self.config.ui.wrap_lines });
-            
+            // Lines come split into rows already; ratatui left to wrap them
+            // would trim their indentation
+            let paragraph = Paragraph::new(lines).block(Block::default().borders(Borders::NONE));
             frame.render_widget(paragraph, area);
             
             // Render cursor
-            if cursor_pos.row >= start_line && cursor_pos.row < end_line {
-                let line_offset = cursor_pos.row - start_line;
-                
-                let col_offset = if self.config.ui.show_line_numbers {
-                    cursor_pos.col + 5 // Account for line numbers
-                } else {
-                    cursor_pos.col
+            if cursor_row >= start_line && cursor_row < end_line {
+                let (line_offset, col_offset) = match wrap_width {
+                    _ if buffer.closed_fold_at(cursor_pos.row).is_some() => (cursor_line_row, gutter),
+                    Some(width) => {
+                        let line = &buffer.content[cursor_pos.row];
+                        let indent = wrap::break_indent(line, width, &self.config.ui);
+                        let (row, col) = wrap::position(line, buffer.shown_hints(cursor_pos.row), cursor_pos.col, width, indent);
+                        (cursor_line_row + row, gutter + col)
+                    }
+                    None => (cursor_line_row, gutter + cursor_cells - left_col),
                 };
                 
                 let cursor_x = area.x + col_offset as u16;
@@ -110,6 +423,7 @@ impl UI {
                 
                 if cursor_x < area.x + area.width && cursor_y < area.y + area.height {
                     frame.set_cursor(cursor_x, cursor_y);
+                    cursor = Some((cursor_x, cursor_y));
                 }
             }
         } else {
@@ -119,6 +433,33 @@ impl UI {
                 .block(Block::default().borders(Borders::NONE));
             frame.render_widget(placeholder, area);
         }
+        cursor
+    }
+    
+    /// The completion popup, under the word being completed or above it
+    /// when there is no room below
+    fn render_completion(&self, frame: &mut Frame, completer: &Completer, (x, y): (u16, u16), area: Rect) {
+        let rows = completer.matches.len().min(COMPLETION_ROWS);
+        let width = completer.matches.iter().map(|word| word.width()).max().unwrap_or(0) + 2;
+        let offset = completer.selected.map_or(0, |selected| (selected + 1).saturating_sub(rows));
+        
+        let x = x.saturating_sub(completer.prefix.width() as u16 + 1).max(area.x);
+        let y = if y + 1 + rows as u16 <= area.y + area.height { y + 1 } else { y.saturating_sub(rows as u16) };
+        let popup = Rect { x, y, width: width as u16, height: rows as u16 }.intersection(area);
+        
+        let lines: Vec<Line> = completer
+            .matches
+            .iter()
+            .enumerate()
+            .skip(offset)
+            .take(rows)
+            .map(|(i, word)| {
+                let style = if completer.selected == Some(i) { COMPLETION_SELECTED } else { COMPLETION_ITEM };
+                Line::from(Span::styled(format!(" {:<1$} ", word, width - 2), style))
+            })
+            .collect();
+        frame.render_widget(Clear, popup);
+        frame.render_widget(Paragraph::new(lines), popup);
     }
     
     fn render_status_line(
@@ -128,9 +469,10 @@ impl UI {
         mode_manager: &ModeManager,
         area: Rect,
     ) {
+        let paste = if mode_manager.paste_mode() { " [PASTE]" } else { "" };
         let mut spans = vec![
             Span::styled(
-                format!(" {} ", mode_manager.current_mode()),
+                format!(" {}{} ", mode_manager.current_mode(), paste),
                 Style::default().bg(Color::Blue).fg(Color::White),
             ),
             Span::raw(" "),
@@ -151,6 +493,29 @@ impl UI {
             // Cursor position
             let pos = buffer.cursor.position();
             spans.push(Span::raw(format!(" {}:{} ", pos.row + 1, pos.col + 1)));
+            
+            // Indent style
+            let indent = buffer.indent.unwrap_or_else(|| DetectedIndent::from_config(&self.config));
+            spans.push(Span::styled(format!("{} ", indent), Style::default().fg(Color::Gray)));
+            
+            // Time since the last write
+            if let Some(saved) = buffer.last_saved.filter(|_| buffer.path.is_some()) {
+                spans.push(Span::styled(
+                    format!("saved {} ", elapsed(saved.elapsed())),
+                    Style::default().fg(Color::Gray),
+                ));
+            }
+            
+            // What the language server says about the cursor line, worst first
+            let diagnostic = buffer
+                .diagnostics
+                .iter()
+                .filter(|d| (d.start.row..=d.end.row).contains(&pos.row))
+                .min_by_key(|d| d.severity);
+            if let Some(diagnostic) = diagnostic {
+                let message = diagnostic.message.lines().next().unwrap_or_default().to_string();
+                spans.push(Span::styled(message, Style::default().fg(severity_color(diagnostic.severity))));
+            }
         }
         
         let status_line = Paragraph::new(Line::from(spans))
@@ -180,4 +545,308 @@ impl UI {
             frame.set_cursor(cursor_x, area.y);
         }
     }
-} 
\ No newline at end of file
+}
+
+/// The centered writing column used by zen mode
+fn zen_column(area: Rect) -> Rect {
+    let width = area.width.min(ZEN_COLUMN_WIDTH);
+    let padding = ZEN_VERTICAL_PADDING.min(area.height.saturating_sub(1) / 2);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + padding,
+        width,
+        height: area.height - 2 * padding,
+    }
+}
+
+/// Split `line` into spans, styling the highlighted ranges on top of `base`
+fn highlight_spans(line: &str, highlights: &[Highlight], base: Style) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut pos = 0;
+    for &(start, end, kind) in highlights {
+        if start > pos {
+            spans.push(Span::styled(line[pos..start].to_string(), base));
+        }
+        spans.push(Span::styled(line[start..end].to_string(), base.patch(syntax_style(kind))));
+        pos = end;
+    }
+    if pos < line.len() {
+        spans.push(Span::styled(line[pos..].to_string(), base));
+    }
+    spans
+}
+
+fn syntax_style(kind: SyntaxKind) -> Style {
+    match kind {
+        SyntaxKind::Keyword => Style::default().fg(Color::Magenta),
+        SyntaxKind::String => Style::default().fg(Color::Green),
+        SyntaxKind::Comment => Style::default().fg(Color::Gray),
+        SyntaxKind::Number => Style::default().fg(Color::Yellow),
+    }
+}
+
+/// The byte range of `line` (row `row`) covered by an inclusive selection
+fn selected_bytes(line: &str, row: usize, (start, end): (Position, Position)) -> Option<(usize, usize)> {
+    if row < start.row || row > end.row {
+        return None;
+    }
+    let from = if row == start.row { byte_index(line, start.col) } else { 0 };
+    let to = if row == end.row { byte_index(line, end.col + 1) } else { line.len() };
+    Some((from, to.max(from)))
+}
+
+/// The byte offset of char column `col` in `line`, or its length past the end
+fn byte_index(line: &str, col: usize) -> usize {
+    line.char_indices().nth(col).map(|(i, _)| i).unwrap_or(line.len())
+}
+
+// Sign column entry for `row`: the worst of the diagnostics starting there
+fn diagnostic_sign(diagnostics: &[&Diagnostic], row: usize, base: Style) -> Span<'static> {
+    match diagnostics.iter().filter(|d| d.start.row == row).map(|d| d.severity).min() {
+        Some(severity) => {
+            let sign = match severity {
+                Severity::Error => "E ",
+                Severity::Warning => "W ",
+                Severity::Information => "I ",
+                Severity::Hint => "H ",
+            };
+            Span::styled(sign, base.fg(severity_color(severity)))
+        }
+        None => Span::styled("  ", base),
+    }
+}
+
+fn severity_color(severity: Severity) -> Color {
+    match severity {
+        Severity::Error => Color::Red,
+        Severity::Warning => Color::Yellow,
+        Severity::Information => Color::Blue,
+        Severity::Hint => Color::Cyan,
+    }
+}
+
+/// The bytes of `line`, row `row`, that `diagnostic` covers. One that is
+/// just a point covers the character there.
+fn diagnostic_bytes(line: &str, row: usize, diagnostic: &Diagnostic) -> (usize, usize) {
+    let from = if row == diagnostic.start.row { byte_index(line, diagnostic.start.col) } else { 0 };
+    let mut to = if row == diagnostic.end.row { byte_index(line, diagnostic.end.col) } else { line.len() };
+    if to <= from && row == diagnostic.start.row {
+        to = byte_index(line, diagnostic.start.col + 1);
+    }
+    (from, to.max(from))
+}
+
+/// The `list` character shown for the character at byte `byte` of a line
+/// whose trailing whitespace starts at `trailing`, if it has one
+fn list_char(c: char, byte: usize, trailing: usize) -> Option<char> {
+    match c {
+        '\t' => Some('>'),
+        '\u{a0}' => Some('+'),
+        ' ' if byte >= trailing => Some('-'),
+        _ => None,
+    }
+}
+
+/// Show the whitespace of `line`, made of `spans`, as `list` characters
+fn show_whitespace(spans: Vec<Span<'static>>, line: &str) -> Vec<Span<'static>> {
+    let trailing = line.trim_end().len();
+    let mut result = Vec::new();
+    let mut offset = 0;
+    for span in spans {
+        let mut part = String::new();
+        for (i, c) in span.content.char_indices() {
+            match list_char(c, offset + i, trailing) {
+                Some(shown) => {
+                    if !part.is_empty() {
+                        result.push(Span::styled(std::mem::take(&mut part), span.style));
+                    }
+                    result.push(Span::styled(shown.to_string(), span.style.patch(LIST_CHARS)));
+                }
+                None => part.push(c),
+            }
+        }
+        offset += span.content.len();
+        if !part.is_empty() {
+            result.push(Span::styled(part, span.style));
+        }
+    }
+    result
+}
+
+/// Split a line made of `spans` into screen rows `width` cells wide for soft
+/// wrapping. Rows after the first start with `indent` blank cells.
+fn wrap_spans(spans: Vec<Span<'static>>, width: usize, indent: usize, base: Style) -> Vec<Vec<Span<'static>>> {
+    let mut layout = wrap::Layout::new(width, indent);
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    for span in spans {
+        let mut part = String::new();
+        for grapheme in span.content.graphemes(true) {
+            if layout.place(wrap::grapheme_width(grapheme)).0 > rows.len() {
+                if !part.is_empty() {
+                    row.push(Span::styled(std::mem::take(&mut part), span.style));
+                }
+                rows.push(std::mem::take(&mut row));
+                if indent > 0 {
+                    row.push(Span::styled(" ".repeat(indent), base));
+                }
+            }
+            part.push_str(grapheme);
+        }
+        if !part.is_empty() {
+            row.push(Span::styled(part, span.style));
+        }
+    }
+    rows.push(row);
+    rows
+}
+
+/// A line made of `spans` with its first `cells` cells scrolled off the left
+/// edge. A wide character cut in two leaves blanks for its visible part.
+fn scroll_spans(spans: Vec<Span<'static>>, cells: usize) -> Vec<Span<'static>> {
+    if cells == 0 {
+        return spans;
+    }
+    let mut skipped = 0;
+    let mut result = Vec::new();
+    for span in spans {
+        if skipped >= cells {
+            result.push(span);
+            continue;
+        }
+        let mut part = String::new();
+        for grapheme in span.content.graphemes(true) {
+            if skipped >= cells {
+                part.push_str(grapheme);
+                continue;
+            }
+            skipped += wrap::grapheme_width(grapheme);
+            if skipped > cells {
+                part.push_str(&" ".repeat(skipped - cells));
+            }
+        }
+        if !part.is_empty() {
+            result.push(Span::styled(part, span.style));
+        }
+    }
+    result
+}
+
+/// Patch `patch` onto the bytes `from..to` of a line made of `spans`
+fn restyle_spans(spans: Vec<Span<'static>>, (from, to): (usize, usize), patch: Style) -> Vec<Span<'static>> {
+    let mut result = Vec::new();
+    let mut offset = 0;
+    for span in spans {
+        let text = span.content.as_ref();
+        let (span_start, span_end) = (offset, offset + text.len());
+        offset = span_end;
+        
+        let start = from.clamp(span_start, span_end) - span_start;
+        let end = to.clamp(span_start, span_end) - span_start;
+        for (part, style) in [
+            (&text[..start], span.style),
+            (&text[start..end], span.style.patch(patch)),
+            (&text[end..], span.style),
+        ] {
+            if !part.is_empty() {
+                result.push(Span::styled(part.to_string(), style));
+            }
+        }
+    }
+    result
+}
+
+/// Place the inlay hints of `line` between the `spans` it is rendered as
+fn insert_hints(spans: Vec<Span<'static>>, line: &str, hints: &[InlayHint], base: Style) -> Vec<Span<'static>> {
+    let mut hints: Vec<(usize, Span<'static>)> = hints
+        .iter()
+        .map(|hint| {
+            let style = match hint.kind {
+                InlayKind::Parameter => Style::default().fg(Color::Gray),
+                InlayKind::Type => Style::default().fg(Color::DarkGray).add_modifier(Modifier::DIM),
+            };
+            (byte_index(line, hint.col), Span::styled(hint.text(), base.patch(style)))
+        })
+        .collect();
+    hints.sort_by_key(|&(byte, _)| byte);
+    let mut hints = hints.into_iter().peekable();
+    
+    let mut result = Vec::new();
+    let mut offset = 0;
+    for span in spans {
+        let text = span.content.as_ref();
+        let mut start = 0;
+        while let Some((byte, hint)) = hints.next_if(|&(byte, _)| byte < offset + text.len()) {
+            let split = byte - offset;
+            if split > start {
+                result.push(Span::styled(text[start..split].to_string(), span.style));
+                start = split;
+            }
+            result.push(hint);
+        }
+        if start < text.len() {
+            result.push(Span::styled(text[start..].to_string(), span.style));
+        }
+        offset += text.len();
+    }
+    result.extend(hints.map(|(_, hint)| hint));
+    result
+}
+
+// A short, coarse duration like `12s ago` or `3m ago`
+fn elapsed(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0..=4 => "just now".to_string(),
+        5..=59 => format!("{}s ago", seconds),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        _ => format!("{}h ago", seconds / 3600),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::{backend::TestBackend, Terminal};
+
+    fn draw(ui: &UI, buffer_manager: &BufferManager, mode_manager: &ModeManager, terminal: &mut Terminal<TestBackend>) {
+        terminal
+            .draw(|frame| ui.render(frame, buffer_manager, mode_manager, frame.size(), false, None))
+            .unwrap();
+    }
+
+    fn row_text(terminal: &Terminal<TestBackend>, row: u16) -> String {
+        let buffer = terminal.backend().buffer();
+        (0..buffer.area.width).map(|col| buffer.get(col, row).symbol.as_str()).collect()
+    }
+
+    #[test]
+    fn resize_keeps_cursor_in_view() {
+        let config = Config::default();
+        let ui = UI::new(&config);
+        let mode_manager = ModeManager::new(&config);
+        let mut buffer_manager = BufferManager::new();
+        buffer_manager.create_buffer("test".to_string());
+        let buffer = buffer_manager.current_buffer_mut().unwrap();
+        buffer.content = (0..50).map(|i| format!("line {i}")).collect();
+        buffer.content[30] = format!("line 30 {}end", "x".repeat(100));
+        buffer.cursor.move_to_position(Position { row: 30, col: 110 });
+
+        let mut terminal = Terminal::new(TestBackend::new(160, 40)).unwrap();
+        draw(&ui, &buffer_manager, &mode_manager, &mut terminal);
+        let buffer = buffer_manager.current_buffer().unwrap();
+        assert_eq!(buffer.top_line.get(), 0);
+        assert_eq!(buffer.left_col.get(), 0);
+
+        // Shrink to 40x10: the left column alone would leave the cursor
+        // 20 rows and 70 columns outside the view
+        buffer_manager.fit_views(9, 40);
+        let buffer = buffer_manager.current_buffer().unwrap();
+        assert_eq!(buffer.top_line.get(), 22);
+        assert_eq!(buffer.left_col.get(), 71);
+
+        let mut terminal = Terminal::new(TestBackend::new(40, 10)).unwrap();
+        draw(&ui, &buffer_manager, &mode_manager, &mut terminal);
+        assert!((0..9).any(|row| row_text(&terminal, row).contains("xend")));
+    }
+}